@@ -5,18 +5,13 @@ use std::env;
 
 fn usage() {
     let mut exe = String::from("client");
-    match env::current_exe() {
-        Ok(ref path) => match path.file_name() {
-            Some(filename) => match filename.to_str() {
-                Some(name) => exe = String::from(name),
-                None => (),
-            },
-            None => {}
-        },
-        Err(_) => (),
-    };
+    if let Ok(ref path) = env::current_exe() {
+        if let Some(name) = path.file_name().and_then(|filename| filename.to_str()) {
+            exe = String::from(name);
+        }
+    }
 
-    print!("Usage: {} [qtype] [qname] <addr>\n", exe);
+    println!("Usage: {} [qtype] [qname] <addr> <udp|tcp>", exe);
     std::process::exit(-1);
 }
 
@@ -33,7 +28,16 @@ fn main() {
         None => String::from("127.0.0.1"),
     };
 
-    let ns = Nameserver::new(&addr, Protocol::UDP);
+    let proto = match args.get(4).map(|arg| arg.to_ascii_lowercase()).as_deref() {
+        None | Some("udp") => Protocol::UDP,
+        Some("tcp") => Protocol::TCP,
+        Some(_) => return usage(),
+    };
+
+    let ns = match Nameserver::parse(&addr, proto) {
+        Some(ns) => ns,
+        None => return usage(),
+    };
 
     let msg = ns.resolve(QName::from_str(qname), qtype);
 
@@ -1,16 +1,22 @@
-use dhns::docker::request::containers_list::ContainersList;
-use dhns::support::Parser;
-use std::os::unix::net::UnixStream;
+use dhns::docker::transport::AnyTransport;
+use dhns::docker::Client;
 
 fn main() {
-    let stream =
-        UnixStream::connect("/var/run/docker.sock").expect("Unable to connect to docker socker");
+    let transport = AnyTransport::from_env().expect("Invalid Docker host settings");
+    let mut client = Client::new(transport);
 
-    let mut request = ContainersList::new(&stream);
+    client
+        .negotiate_version()
+        .expect("Unable to negotiate API version");
 
-    let response = request.exec().unwrap();
+    let (containers, skipped) = client.containers_list().expect("Unable to list containers");
+    for err in skipped {
+        println!("Skipping container: {}", err);
+    }
+    let (networks, skipped) = client.networks_list().expect("Unable to list networks");
+    for err in skipped {
+        println!("Skipping network: {}", err);
+    }
 
-    let json = Parser::parse(Vec::from(response));
-
-    dbg!(json.unwrap());
+    dbg!(containers, networks);
 }
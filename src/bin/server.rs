@@ -1,17 +1,351 @@
+use dhns::dns::client::{Nameserver, Protocol};
+use dhns::dns::cookie::{self, Cookies, Verdict};
+use dhns::dns::forwarder::Forwarder;
+use dhns::dns::proto::edns;
+use dhns::dns::proto::framing::{read_frame, write_frame};
 use dhns::dns::proto::message::Message;
+use dhns::dns::proto::rcode::Rcode;
 use dhns::dns::resolver::Resolver;
-use std::net::UdpSocket;
+use dhns::docker::error::DockerResult;
+use dhns::docker::events::Event;
+use dhns::docker::transport::AnyTransport;
+use dhns::docker::Client;
+use std::env;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+/// Largest query datagram accepted, the size of the receive buffer
 const UDPV4_DNS_MAX: usize = 1500;
 
+/// UDP payload size we advertise and send at most, small enough to avoid
+/// IP fragmentation on common paths (DNS flag day 2020)
+const EDNS_PAYLOAD_SIZE: u16 = 1232;
+
+const LISTEN_ADDR: &str = "127.0.0.1:1053";
+
+/// How long a TCP connection may sit without a query before it is closed
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TCP connections served at once, more are closed right after being accepted
+const TCP_MAX_CONNECTIONS: usize = 64;
+
+const DNS_ZONE: &str = "docker.";
+
+/// Comma separated nameservers to forward queries outside the zone to,
+/// IPv4 or IPv6 addresses with an optional port
+const UPSTREAMS_ENV: &str = "DHNS_UPSTREAMS";
+
+/// Comma separated domains outside the zone containers may claim names in
+const FOREIGN_SUFFIXES_ENV: &str = "DHNS_FOREIGN_SUFFIXES";
+
+/// Memory cap of the cache of forwarded responses, in bytes
+const CACHE_SIZE_ENV: &str = "DHNS_CACHE_SIZE";
+
+/// Delay before reconnecting to Docker after the events stream ends
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Threads relaying UDP queries upstream, off the receive loop
+const FORWARD_WORKERS: usize = 8;
+
+/// UDP queries waiting for a forwarding thread, more fail with SERVFAIL right away
+const FORWARD_QUEUE: usize = 64;
+
+/// UDP query waiting to be forwarded upstream
+struct Pending {
+    qry: Message,
+    src: SocketAddr,
+    max: usize,
+}
+
+/// Subscribes to events first and lists containers after,
+/// so that nothing starting in between goes unnoticed
+fn watch_events(transport: &AnyTransport, resolver: &RwLock<Resolver>) -> DockerResult<()> {
+    let mut client = Client::new(transport.clone());
+    println!("Using Docker API v{}", client.negotiate_version()?);
+
+    let events = client.events()?;
+
+    let (containers, skipped) = client.containers_list()?;
+    for err in skipped {
+        println!("Skipping container: {}", err);
+    }
+    resolver.write().unwrap().load(&containers);
+
+    for event in events {
+        match event? {
+            Event::Update(id) => match client.container_inspect(&id) {
+                Ok(container) => resolver.write().unwrap().update(&container),
+                Err(err) => println!("Error inspecting container {}: {}", id, err),
+            },
+            Event::Remove(id) => resolver.write().unwrap().remove(&id),
+        }
+    }
+
+    Ok(())
+}
+
+fn upstreams() -> Result<Vec<Nameserver>, String> {
+    let upstreams = env::var(UPSTREAMS_ENV).unwrap_or_default();
+
+    upstreams
+        .split(',')
+        .map(|addr| addr.trim())
+        .filter(|addr| !addr.is_empty())
+        .map(|addr| match Nameserver::parse(addr, Protocol::UDP) {
+            Some(ns) => Ok(ns),
+            None => Err(format!(
+                "Invalid upstream nameserver {} in {}",
+                addr, UPSTREAMS_ENV
+            )),
+        })
+        .collect()
+}
+
+/// Cache size from the environment, None to keep the default
+fn cache_size() -> Result<Option<usize>, String> {
+    let size = env::var(CACHE_SIZE_ENV).unwrap_or_default();
+    if size.trim().is_empty() {
+        return Ok(None);
+    }
+
+    match size.trim().parse() {
+        Ok(size) => Ok(Some(size)),
+        Err(_) => Err(format!("Invalid cache size {} in {}", size, CACHE_SIZE_ENV)),
+    }
+}
+
+/// Answers from the resolver if it owns the question,
+/// None if the query has to be forwarded upstream
+fn answer_local(
+    resolver: &RwLock<Resolver>,
+    forwarder: &Forwarder,
+    qry: &Message,
+) -> Option<Message> {
+    if let Some(reply) = edns::check_version(qry, EDNS_PAYLOAD_SIZE) {
+        return Some(reply);
+    }
+
+    if forwarder.is_enabled() && !resolver.read().unwrap().is_authoritative(qry) {
+        return None;
+    }
+
+    let mut reply = resolver.read().unwrap().resolve(qry.clone());
+    edns::echo(qry, &mut reply, EDNS_PAYLOAD_SIZE);
+    Some(reply)
+}
+
+/// Answers from upstream, the resolver lock is not held while waiting on it
+fn answer_upstream(forwarder: &Forwarder, qry: &Message) -> Message {
+    let (mut reply, failures) = forwarder.forward(qry);
+    for (upstream, err) in failures {
+        println!("Upstream {} failed: {}", upstream, err);
+    }
+    edns::echo(qry, &mut reply, EDNS_PAYLOAD_SIZE);
+    reply
+}
+
+/// Answers from the resolver if it owns the question, from upstream otherwise
+fn answer(resolver: &RwLock<Resolver>, forwarder: &Forwarder, qry: &Message) -> Message {
+    answer_local(resolver, forwarder, qry).unwrap_or_else(|| answer_upstream(forwarder, qry))
+}
+
+/// SERVFAIL response for a query that could not be forwarded
+fn servfail(qry: &Message) -> Message {
+    let mut reply = Message::response_for(qry);
+    reply.set_rcode(Rcode::SERVFAIL);
+    edns::echo(qry, &mut reply, EDNS_PAYLOAD_SIZE);
+    reply
+}
+
+/// Adds a server cookie to the reply to a UDP query and sends it within `max` bytes
+fn send_udp(
+    sock: &UdpSocket,
+    cookies: &Mutex<Cookies>,
+    qry: &Message,
+    mut ans: Message,
+    src: SocketAddr,
+    max: usize,
+) {
+    cookies
+        .lock()
+        .unwrap()
+        .sign(qry, &mut ans, src.ip(), unix_time());
+
+    let mut res: Vec<u8> = vec![];
+    ans.write_within(&mut res, max);
+
+    match sock.send_to(&res[..], src) {
+        Ok(sz) => println!("Sent {} bytes in response", sz),
+        Err(err) => println!("Error sending response: {}", err),
+    }
+}
+
+/// Relays queued UDP queries upstream and replies to them
+fn forward_udp(
+    sock: UdpSocket,
+    queue: Arc<Mutex<Receiver<Pending>>>,
+    forwarder: Arc<Forwarder>,
+    cookies: Arc<Mutex<Cookies>>,
+) {
+    loop {
+        // the queue is only locked while waiting, not while forwarding
+        let pending = queue.lock().unwrap().recv();
+        let pending = match pending {
+            Ok(pending) => pending,
+            Err(_) => return,
+        };
+
+        let ans = answer_upstream(&forwarder, &pending.qry);
+        send_udp(&sock, &cookies, &pending.qry, ans, pending.src, pending.max);
+    }
+}
+
+/// Serves queries on a TCP connection until the client closes it or goes idle
+fn serve_tcp(
+    mut stream: TcpStream,
+    resolver: &RwLock<Resolver>,
+    forwarder: &Forwarder,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TCP_IDLE_TIMEOUT))?;
+    stream.set_write_timeout(Some(TCP_IDLE_TIMEOUT))?;
+
+    while let Some(data) = read_frame(&mut stream)? {
+        let qry = match Message::read(&data) {
+            Ok(qry) => qry,
+            Err(err) => {
+                println!("Error reading message: {}", err);
+                match Message::format_error(&data) {
+                    Some(ans) => {
+                        let mut res: Vec<u8> = vec![];
+                        ans.write(&mut res);
+                        write_frame(&mut stream, &res)?;
+                        continue;
+                    }
+                    None => return Ok(()),
+                }
+            }
+        };
+
+        let ans = answer(resolver, forwarder, &qry);
+        let mut res: Vec<u8> = vec![];
+        ans.write(&mut res);
+
+        write_frame(&mut stream, &res)?;
+    }
+
+    Ok(())
+}
+
+fn listen_tcp(listener: TcpListener, resolver: Arc<RwLock<Resolver>>, forwarder: Arc<Forwarder>) {
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if connections.fetch_add(1, Ordering::SeqCst) >= TCP_MAX_CONNECTIONS {
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                    println!("Too many TCP connections, closing one");
+                    continue;
+                }
+
+                let resolver = Arc::clone(&resolver);
+                let forwarder = Arc::clone(&forwarder);
+                let connections = Arc::clone(&connections);
+                thread::spawn(move || {
+                    match serve_tcp(stream, &resolver, &forwarder) {
+                        Ok(()) => {}
+                        // the client went idle
+                        Err(err)
+                            if err.kind() == ErrorKind::WouldBlock
+                                || err.kind() == ErrorKind::TimedOut => {}
+                        Err(err) => println!("TCP connection error: {}", err),
+                    }
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+            Err(err) => println!("accept error: {}", err),
+        }
+    }
+}
+
+/// Seconds since the epoch, as carried in server cookies
+fn unix_time() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as u32)
+        .unwrap_or(0)
+}
+
 fn main() {
-    let sock = UdpSocket::bind("127.0.0.1:1053").expect("Unable to listen on 127.0.0.1:1053");
+    let sock = UdpSocket::bind(LISTEN_ADDR).expect("Unable to listen on UDP");
+    let listener = TcpListener::bind(LISTEN_ADDR).expect("Unable to listen on TCP");
     let mut buf = [0; UDPV4_DNS_MAX + 1];
 
-    let resolver = Resolver::new();
+    let transport = AnyTransport::from_env().expect("Invalid Docker host settings");
+
+    let mut resolver = Resolver::new(DNS_ZONE);
+    // published ports of a remote engine are reachable on its host,
+    // SRV targets have to be names though
+    if let Some(host) = transport.host() {
+        if host.parse::<IpAddr>().is_err() {
+            resolver.set_host(&format!("{}.", host.trim_end_matches('.')));
+        }
+    }
+    let suffixes = env::var(FOREIGN_SUFFIXES_ENV).unwrap_or_default();
+    resolver.set_foreign_suffixes(&suffixes.split(',').map(str::trim).collect::<Vec<&str>>());
+    let resolver = Arc::new(RwLock::new(resolver));
+
+    let upstreams = match upstreams() {
+        Ok(upstreams) => upstreams,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let mut forwarder = Forwarder::new(upstreams);
+    match cache_size() {
+        Ok(Some(size)) => forwarder.set_cache_size(size),
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+    let forwarder = Arc::new(forwarder);
+
+    let watcher = Arc::clone(&resolver);
+    thread::spawn(move || loop {
+        match watch_events(&transport, &watcher) {
+            Ok(()) => println!("Docker events stream ended"),
+            Err(err) => println!("Docker events error: {}", err),
+        }
+        thread::sleep(RECONNECT_DELAY);
+    });
+
+    let (tcp_resolver, tcp_forwarder) = (Arc::clone(&resolver), Arc::clone(&forwarder));
+    thread::spawn(move || listen_tcp(listener, tcp_resolver, tcp_forwarder));
 
     println!("Listening on {}", sock.local_addr().unwrap());
 
+    let cookies = Arc::new(Mutex::new(Cookies::new(unix_time())));
+
+    let (queue, pending) = mpsc::sync_channel(FORWARD_QUEUE);
+    let pending = Arc::new(Mutex::new(pending));
+    for _ in 0..FORWARD_WORKERS {
+        let sock = sock.try_clone().expect("Unable to share the UDP socket");
+        let (pending, forwarder, cookies) = (
+            Arc::clone(&pending),
+            Arc::clone(&forwarder),
+            Arc::clone(&cookies),
+        );
+        thread::spawn(move || forward_udp(sock, pending, forwarder, cookies));
+    }
+
     loop {
         match sock.recv_from(&mut buf) {
             Ok((amt, _)) if amt > UDPV4_DNS_MAX => println!(
@@ -25,16 +359,39 @@ fn main() {
                     Ok(qry) => {
                         println!("Questions from {}: {:#?}", src, qry.questions());
 
-                        let ans = resolver.resolve(qry);
-                        let mut res: Vec<u8> = vec![];
-                        ans.write(&mut res);
+                        let max = qry.udp_payload_size().min(EDNS_PAYLOAD_SIZE as usize);
+                        let verdict = cookies.lock().unwrap().check(&qry, src.ip(), unix_time());
+
+                        // bad cookies are not logged, spoofed datagrams would flood the log
+                        let ans = match verdict {
+                            Verdict::Invalid => Some(cookie::bad_cookie(&qry, EDNS_PAYLOAD_SIZE)),
+                            _ => answer_local(&resolver, &forwarder, &qry),
+                        };
 
-                        match sock.send_to(&res[..], src) {
-                            Ok(sz) => println!("Sent {} bytes in response", sz),
-                            Err(err) => println!("Error sending response: {}", err),
+                        match ans {
+                            Some(ans) => send_udp(&sock, &cookies, &qry, ans, src, max),
+                            None => {
+                                if let Err(err) = queue.try_send(Pending { qry, src, max }) {
+                                    let Pending { qry, .. } = match err {
+                                        TrySendError::Full(pending) => pending,
+                                        TrySendError::Disconnected(pending) => pending,
+                                    };
+                                    println!("Forwarding queue full, failing query from {}", src);
+                                    send_udp(&sock, &cookies, &qry, servfail(&qry), src, max);
+                                }
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        println!("Error reading message: {}", err);
+                        if let Some(ans) = Message::format_error(&data) {
+                            let mut res: Vec<u8> = vec![];
+                            ans.write(&mut res);
+                            if let Err(err) = sock.send_to(&res[..], src) {
+                                println!("Error sending response: {}", err);
+                            }
                         }
                     }
-                    Err(err) => println!("Error reading message: {}", err),
                 }
             }
             Err(err) => println!("recv_from error: {}", err),
@@ -0,0 +1,34 @@
+use crate::dns::proto::error::ProtoErr;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ClientErr {
+    Io(std::io::Error),
+    /// Nameserver answered with a malformed message
+    Proto(ProtoErr),
+}
+
+pub type ClientResult<T> = Result<T, ClientErr>;
+
+impl fmt::Display for ClientErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientErr::Io(err) => write!(f, "I/O error: {}", err),
+            ClientErr::Proto(err) => write!(f, "Malformed response: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ClientErr {}
+
+impl From<std::io::Error> for ClientErr {
+    fn from(err: std::io::Error) -> ClientErr {
+        ClientErr::Io(err)
+    }
+}
+
+impl From<ProtoErr> for ClientErr {
+    fn from(err: ProtoErr) -> ClientErr {
+        ClientErr::Proto(err)
+    }
+}
@@ -1,11 +1,24 @@
-use std::net::Ipv4Addr;
-use std::net::UdpSocket;
+pub mod error;
 
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::dns::client::error::{ClientErr, ClientResult};
+use crate::dns::proto::framing::{read_frame, write_frame};
 use crate::dns::proto::message::Message;
 use crate::dns::proto::qname::QName;
 use crate::dns::proto::qtype::QType;
 use crate::dns::proto::question::Question;
 
+const DNS_PORT: u16 = 53;
+
+/// Largest response accepted, EDNS lets nameservers go beyond 512 bytes
+const UDP_MAX: usize = 4096;
+
+/// How long to wait for a nameserver to answer
+const TIMEOUT: Duration = Duration::from_secs(2);
+
 #[derive(Debug)]
 pub enum Protocol {
     UDP,
@@ -14,36 +27,176 @@ pub enum Protocol {
 
 #[derive(Debug)]
 pub struct Nameserver {
-    addr: Ipv4Addr,
+    addr: SocketAddr,
     proto: Protocol,
 }
 
 impl Nameserver {
-    pub fn new(addr: &str, proto: Protocol) -> Nameserver {
-        let ip_addr = addr.parse().unwrap();
-        Nameserver {
-            addr: ip_addr,
-            proto,
-        }
+    pub fn new(addr: SocketAddr, proto: Protocol) -> Nameserver {
+        Nameserver { addr, proto }
+    }
+
+    /// Nameserver at `192.0.2.1`, `2001:db8::1`, `192.0.2.1:5353` or `[2001:db8::1]:5353`,
+    /// on port 53 unless given
+    pub fn parse(addr: &str, proto: Protocol) -> Option<Nameserver> {
+        let addr = match addr.parse::<SocketAddr>() {
+            Ok(addr) => addr,
+            Err(_) => SocketAddr::new(addr.parse::<IpAddr>().ok()?, DNS_PORT),
+        };
+
+        Some(Nameserver::new(addr, proto))
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
     }
 
-    pub fn resolve(&self, qname: QName, qtype: QType) -> Result<Message, &str> {
+    pub fn resolve(&self, qname: QName, qtype: QType) -> ClientResult<Message> {
         let mut msg = Message::new();
+        msg.header_mut().set_rd(true);
         msg.ask(Question::new(qname, qtype, None));
 
+        self.exchange(&msg)
+    }
+
+    /// Sends a query as is, keeping its ID, and waits for the matching response:
+    /// QR set, same ID and same questions. Truncated UDP responses are retried over TCP.
+    pub fn exchange(&self, msg: &Message) -> ClientResult<Message> {
         let mut buf: Vec<u8> = vec![];
         msg.write(&mut buf);
 
-        let sock = UdpSocket::bind("0.0.0.0:0").unwrap();
+        match self.proto {
+            Protocol::UDP => match self.exchange_udp(msg, &buf)? {
+                reply if reply.header().tc() => self.exchange_tcp(msg, &buf),
+                reply => Ok(reply),
+            },
+            Protocol::TCP => self.exchange_tcp(msg, &buf),
+        }
+    }
+
+    fn exchange_udp(&self, msg: &Message, buf: &[u8]) -> ClientResult<Message> {
+        let local = match self.addr {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let sock = UdpSocket::bind(local)?;
+        // only accept datagrams from the nameserver itself
+        sock.connect(self.addr)?;
+
+        sock.send(buf)?;
+
+        let deadline = Instant::now() + TIMEOUT;
+        let mut data = [0u8; UDP_MAX];
+        loop {
+            // stray or spoofed datagrams don't extend the wait
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left == Duration::from_secs(0) {
+                let err = Error::new(ErrorKind::TimedOut, "no response from nameserver");
+                return Err(ClientErr::Io(err));
+            }
+            sock.set_read_timeout(Some(left))?;
+
+            let len = sock.recv(&mut data)?;
+
+            // stray, spoofed or garbled responses are dropped
+            match Message::read(&data[..len]) {
+                Ok(reply) if reply.is_response_to(msg) => return Ok(reply),
+                _ => continue,
+            }
+        }
+    }
+
+    fn exchange_tcp(&self, msg: &Message, buf: &[u8]) -> ClientResult<Message> {
+        let mut stream = TcpStream::connect_timeout(&self.addr, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        write_frame(&mut stream, buf)?;
+
+        let data = match read_frame(&mut stream)? {
+            Some(data) => data,
+            None => {
+                let err = Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "connection closed without a response",
+                );
+                return Err(ClientErr::Io(err));
+            }
+        };
+
+        let reply = Message::read(&data)?;
+        if !reply.is_response_to(msg) {
+            let err = Error::new(ErrorKind::InvalidData, "response does not match the query");
+            return Err(ClientErr::Io(err));
+        }
+
+        Ok(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let addr = |s: &str| Nameserver::parse(s, Protocol::UDP).map(|ns| ns.addr());
+
+        assert_eq!(
+            Some(SocketAddr::from(([192, 0, 2, 1], 53))),
+            addr("192.0.2.1")
+        );
+        assert_eq!(
+            Some(SocketAddr::from(([192, 0, 2, 1], 5353))),
+            addr("192.0.2.1:5353")
+        );
+        assert_eq!(
+            Some("[2001:db8::1]:53".parse().unwrap()),
+            addr("2001:db8::1")
+        );
+        assert_eq!(
+            Some("[2001:db8::1]:5353".parse().unwrap()),
+            addr("[2001:db8::1]:5353")
+        );
+        assert!(addr("ns.example.com").is_none());
+        assert!(addr("192.0.2.1:dns").is_none());
+    }
+
+    #[test]
+    fn test_exchange_udp_skips_garbage() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let ns = Nameserver::new(server.local_addr().unwrap(), Protocol::UDP);
+
+        let mut query = Message::new();
+        query.ask(Question::new(QName::from_str("web.docker"), QType::A, None));
+
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; UDP_MAX];
+            let (len, src) = server.recv_from(&mut buf).unwrap();
+            let query = Message::read(&buf[..len]).unwrap();
 
-        let mut dest = self.addr.to_string();
-        dest.push_str(":53");
+            let mut other = Message::response_for(&query);
+            other
+                .header_mut()
+                .set_id(query.header().id().wrapping_add(1));
+            let mut elsewhere = Message::response_for(&query);
+            elsewhere.questions_mut()[0].qname = QName::from_str("bank.example");
+            let mut reply = Message::response_for(&query);
+            reply.header_mut().set_aa(true);
 
-        sock.send_to(&buf, dest).unwrap();
+            server.send_to(&[0xFF; 5], src).unwrap();
+            // the query echoed back, a wrong ID and a wrong question come first
+            for msg in [query, other, elsewhere, reply].iter() {
+                let mut buf = vec![];
+                msg.write(&mut buf);
+                server.send_to(&buf, src).unwrap();
+            }
+        });
 
-        let mut data = [0u8; 512];
-        let len = sock.recv(&mut data).unwrap();
+        let reply = ns.exchange(&query).unwrap();
+        assert_eq!(query.header().id(), reply.header().id());
+        assert!(reply.header().aa());
 
-        Message::read(&data[..len].to_vec())
+        responder.join().unwrap();
     }
 }
@@ -0,0 +1,375 @@
+mod siphash;
+
+use crate::dns::proto::edns::{self, EdnsOption};
+use crate::dns::proto::message::Message;
+use crate::dns::proto::rcode::Rcode;
+use crate::dns::proto::record::Record;
+use rand::prelude::*;
+use siphash::siphash24;
+use std::net::IpAddr;
+
+/// Server cookie layout of RFC 9018
+const COOKIE_VERSION: u8 = 1;
+const SERVER_COOKIE_LEN: usize = 16;
+
+/// How long a server cookie is accepted after it was issued
+const COOKIE_LIFETIME: u32 = 3600;
+
+/// How far in the future a timestamp may be, for clock skew between servers
+const COOKIE_SKEW: u32 = 300;
+
+/// How long a secret is used for new cookies, it is accepted for one more period
+const SECRET_LIFETIME: u32 = 86400;
+
+/// What the COOKIE option of a query tells about its source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    /// No COOKIE option, the client does not support cookies
+    Missing,
+    /// Client cookie only, as in a first query
+    ClientOnly,
+    /// Server cookie we issued to this address not long ago
+    Valid,
+    /// Server cookie we did not issue, issued to another address or expired
+    Invalid,
+}
+
+/// Issues and checks DNS server cookies, RFC 7873 and RFC 9018.
+///
+/// A server cookie is a keyed hash of the client cookie, the client address
+/// and a timestamp, so an off-path attacker can not forge one for an address
+/// it does not receive responses on.
+pub struct Cookies {
+    secret: [u8; 16],
+    previous: Option<[u8; 16]>,
+    rotated: u32,
+}
+
+impl Cookies {
+    /// Starts with a random secret, `now` being the time in seconds since the epoch
+    pub fn new(now: u32) -> Cookies {
+        Cookies {
+            secret: random(),
+            previous: None,
+            rotated: now,
+        }
+    }
+
+    /// Checks the cookies of a query from `client`
+    pub fn check(&mut self, query: &Message, client: IpAddr, now: u32) -> Verdict {
+        self.rotate(now);
+
+        let (client_cookie, server_cookie) = match cookie(query) {
+            Some(cookie) => cookie,
+            None => return Verdict::Missing,
+        };
+        if server_cookie.is_empty() {
+            return Verdict::ClientOnly;
+        }
+        if server_cookie.len() != SERVER_COOKIE_LEN || server_cookie[0] != COOKIE_VERSION {
+            return Verdict::Invalid;
+        }
+
+        let issued = u32::from_be_bytes([
+            server_cookie[4],
+            server_cookie[5],
+            server_cookie[6],
+            server_cookie[7],
+        ]);
+        // serial number arithmetic, timestamps wrap around in 2106
+        if now.wrapping_sub(issued) > COOKIE_LIFETIME && issued.wrapping_sub(now) > COOKIE_SKEW {
+            return Verdict::Invalid;
+        }
+
+        let valid = Some(&self.secret)
+            .into_iter()
+            .chain(self.previous.as_ref())
+            .any(|secret| server_cookie == &server(secret, client_cookie, client, issued)[..]);
+
+        if valid {
+            Verdict::Valid
+        } else {
+            Verdict::Invalid
+        }
+    }
+
+    /// Adds a fresh server cookie to a response if the query had a client cookie
+    /// and the response has an OPT record
+    pub fn sign(&self, query: &Message, reply: &mut Message, client: IpAddr, now: u32) {
+        let client_cookie = match cookie(query) {
+            Some((client_cookie, _)) => *client_cookie,
+            None => return,
+        };
+
+        let mut opt = match reply.edns() {
+            Some(opt) => opt.clone(),
+            None => return,
+        };
+        if let Record::Option { options, .. } = &mut opt {
+            options.retain(|option| !matches!(option, EdnsOption::Cookie { .. }));
+            options.push(EdnsOption::Cookie {
+                client: client_cookie,
+                server: server(&self.secret, &client_cookie, client, now).to_vec(),
+            });
+        }
+        reply.set_edns(Some(opt));
+    }
+
+    /// Starts a new secret once the current one is old enough
+    fn rotate(&mut self, now: u32) {
+        if now.wrapping_sub(self.rotated) >= SECRET_LIFETIME {
+            self.previous = Some(self.secret);
+            self.secret = random();
+            self.rotated = now;
+        }
+    }
+}
+
+/// Our client cookie with an upstream nameserver and the last server cookie
+/// it gave us, so that queries relayed upstream carry our cookies and never
+/// those of the client, RFC 7873 section 5.1
+#[derive(Debug)]
+pub struct ClientCookie {
+    client: [u8; 8],
+    server: Vec<u8>,
+}
+
+impl Default for ClientCookie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientCookie {
+    pub fn new() -> ClientCookie {
+        ClientCookie {
+            client: random(),
+            server: vec![],
+        }
+    }
+
+    /// Copy of a query with its COOKIE option replaced by ours,
+    /// one without EDNS is left as it is
+    pub fn attach(&self, query: &Message) -> Message {
+        let mut query = query.clone();
+
+        let mut opt = match query.edns() {
+            Some(opt) => opt.clone(),
+            None => return query,
+        };
+        if let Record::Option { options, .. } = &mut opt {
+            options.retain(|option| !matches!(option, EdnsOption::Cookie { .. }));
+            options.push(EdnsOption::Cookie {
+                client: self.client,
+                server: self.server.clone(),
+            });
+        }
+        query.set_edns(Some(opt));
+
+        query
+    }
+
+    /// Remembers the server cookie of a response to our client cookie
+    pub fn learn(&mut self, reply: &Message) {
+        if let Some((client, server)) = cookie(reply) {
+            if *client == self.client && !server.is_empty() {
+                self.server = server.to_vec();
+            }
+        }
+    }
+}
+
+/// BADCOOKIE response, carrying no answers, for the client to retry
+/// with the server cookie `Cookies::sign` adds
+pub fn bad_cookie(query: &Message, payload_size: u16) -> Message {
+    let mut reply = Message::response_for(query);
+    reply.set_edns(Some(edns::opt(payload_size, 0)));
+    reply.set_rcode(Rcode::BADCOOKIE);
+    reply
+}
+
+/// Client and server cookie of a query, the latter empty in a first query
+fn cookie(msg: &Message) -> Option<(&[u8; 8], &[u8])> {
+    match msg.edns() {
+        Some(Record::Option { options, .. }) => options.iter().find_map(|option| match option {
+            EdnsOption::Cookie { client, server } => Some((client, server.as_slice())),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Server cookie: version, 3 reserved bytes, timestamp and hash, RFC 9018 section 4
+fn server(
+    secret: &[u8; 16],
+    client_cookie: &[u8; 8],
+    client: IpAddr,
+    timestamp: u32,
+) -> [u8; SERVER_COOKIE_LEN] {
+    let mut cookie = [0u8; SERVER_COOKIE_LEN];
+    cookie[0] = COOKIE_VERSION;
+    cookie[4..8].copy_from_slice(&timestamp.to_be_bytes());
+
+    let mut data = client_cookie.to_vec();
+    data.extend_from_slice(&cookie[..8]);
+    match client {
+        IpAddr::V4(addr) => data.extend_from_slice(&addr.octets()),
+        IpAddr::V6(addr) => data.extend_from_slice(&addr.octets()),
+    }
+
+    cookie[8..].copy_from_slice(&siphash24(secret, &data).to_le_bytes());
+    cookie
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::proto::qname::QName;
+    use crate::dns::proto::qtype::QType;
+    use crate::dns::proto::question::Question;
+
+    const NOW: u32 = 1_700_000_000;
+    const CLIENT_COOKIE: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn query(cookie: Option<Vec<u8>>) -> Message {
+        let mut msg = Message::new();
+        msg.ask(Question::new(QName::from_str("web.docker"), QType::A, None));
+
+        let mut opt = edns::opt(1232, 0);
+        if let (Record::Option { options, .. }, Some(server)) = (&mut opt, cookie) {
+            options.push(EdnsOption::Cookie {
+                client: CLIENT_COOKIE,
+                server,
+            });
+        }
+        msg.set_edns(Some(opt));
+        msg
+    }
+
+    /// Server cookie handed out in response to a first query
+    fn issue(cookies: &Cookies, client: IpAddr, now: u32) -> Vec<u8> {
+        let first = query(Some(vec![]));
+        let mut reply = Message::response_for(&first);
+        reply.set_edns(Some(edns::opt(1232, 0)));
+        cookies.sign(&first, &mut reply, client, now);
+
+        match cookie(&reply) {
+            Some((client_cookie, server)) => {
+                assert_eq!(&CLIENT_COOKIE, client_cookie);
+                server.to_vec()
+            }
+            None => panic!("No cookie in {:?}", reply),
+        }
+    }
+
+    #[test]
+    fn test_verdicts() {
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut cookies = Cookies::new(NOW);
+
+        assert_eq!(Verdict::Missing, cookies.check(&query(None), client, NOW));
+        assert_eq!(
+            Verdict::ClientOnly,
+            cookies.check(&query(Some(vec![])), client, NOW)
+        );
+
+        let server = issue(&cookies, client, NOW);
+        assert_eq!(SERVER_COOKIE_LEN, server.len());
+        assert_eq!(
+            Verdict::Valid,
+            cookies.check(&query(Some(server.clone())), client, NOW + 60)
+        );
+
+        // replayed from another address
+        let other: IpAddr = "2001:db8::1".parse().unwrap();
+        assert_eq!(
+            Verdict::Invalid,
+            cookies.check(&query(Some(server.clone())), other, NOW + 60)
+        );
+
+        // tampered with
+        let mut forged = server.clone();
+        forged[15] ^= 1;
+        assert_eq!(
+            Verdict::Invalid,
+            cookies.check(&query(Some(forged)), client, NOW + 60)
+        );
+
+        // expired
+        assert_eq!(
+            Verdict::Invalid,
+            cookies.check(&query(Some(server)), client, NOW + COOKIE_LIFETIME + 1)
+        );
+    }
+
+    #[test]
+    fn test_rotation() {
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut cookies = Cookies::new(NOW);
+
+        let before = NOW + SECRET_LIFETIME - 10;
+        let server = issue(&cookies, client, before);
+
+        // still valid right after the secret changed
+        let after = NOW + SECRET_LIFETIME;
+        assert_eq!(
+            Verdict::Valid,
+            cookies.check(&query(Some(server.clone())), client, after)
+        );
+        assert_ne!(server, issue(&cookies, client, before));
+    }
+
+    #[test]
+    fn test_client_cookie() {
+        let mut ours = ClientCookie::new();
+
+        // the client's cookies never go upstream
+        let qry = query(Some(vec![9; SERVER_COOKIE_LEN]));
+        let relayed = ours.attach(&qry);
+        assert_eq!(Some((&ours.client, &[][..])), cookie(&relayed));
+        assert_eq!(qry.header().id(), relayed.header().id());
+
+        // a server cookie given to someone else is ignored
+        let mut reply = Message::response_for(&qry);
+        reply.set_edns(qry.edns().cloned());
+        ours.learn(&reply);
+        assert!(ours.server.is_empty());
+
+        reply.set_edns(relayed.edns().cloned());
+        if let Some(Record::Option { options, .. }) = reply.additional_mut().last_mut() {
+            options[0] = EdnsOption::Cookie {
+                client: ours.client,
+                server: vec![7; SERVER_COOKIE_LEN],
+            };
+        }
+        ours.learn(&reply);
+        assert_eq!(
+            Some((&ours.client, &[7; SERVER_COOKIE_LEN][..])),
+            cookie(&ours.attach(&qry))
+        );
+
+        // no EDNS, no cookie
+        let mut plain = query(None);
+        plain.set_edns(None);
+        assert_eq!(None, cookie(&ours.attach(&plain)));
+    }
+
+    #[test]
+    fn test_bad_cookie() {
+        let qry = query(Some(vec![0; SERVER_COOKIE_LEN]));
+        let mut reply = bad_cookie(&qry, 1232);
+        Cookies::new(NOW).sign(&qry, &mut reply, "192.0.2.1".parse().unwrap(), NOW);
+
+        assert_eq!(Rcode::BADCOOKIE, reply.rcode());
+        assert!(reply.answers().is_empty());
+        match reply.edns() {
+            Some(Record::Option {
+                ext_rcode, options, ..
+            }) => {
+                assert_eq!(Rcode::BADCOOKIE.ext_part(), *ext_rcode);
+                assert_eq!(1, options.len());
+            }
+            other => panic!("Expected OPT record, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::convert::TryInto;
+
+/// SipHash-2-4 of `data` under a 128 bit key
+pub fn siphash24(key: &[u8; 16], data: &[u8]) -> u64 {
+    let k0 = u64::from_le_bytes(key[..8].try_into().unwrap());
+    let k1 = u64::from_le_bytes(key[8..].try_into().unwrap());
+
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in chunks.by_ref() {
+        compress(&mut v, u64::from_le_bytes(chunk.try_into().unwrap()), 2);
+    }
+
+    // the last block holds the remaining bytes and the length in its top byte
+    let mut last = (data.len() as u64) << 56;
+    for (i, byte) in chunks.remainder().iter().enumerate() {
+        last |= (*byte as u64) << (8 * i);
+    }
+    compress(&mut v, last, 2);
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+fn compress(v: &mut [u64; 4], m: u64, rounds: usize) {
+    v[3] ^= m;
+    for _ in 0..rounds {
+        round(v);
+    }
+    v[0] ^= m;
+}
+
+fn round(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(13) ^ v[0];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(16) ^ v[2];
+    v[0] = v[0].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(21) ^ v[0];
+    v[2] = v[2].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(17) ^ v[2];
+    v[2] = v[2].rotate_left(32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_vectors() {
+        let mut key = [0u8; 16];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let data: Vec<u8> = (0..64).collect();
+
+        // from the SipHash paper and its reference implementation
+        assert_eq!(0x726f_db47_dd0e_0e31, siphash24(&key, &[]));
+        assert_eq!(0xa129_ca61_49be_45e5, siphash24(&key, &data[..15]));
+        assert_eq!(0x958a_324c_eb06_4572, siphash24(&key, &data[..63]));
+    }
+}
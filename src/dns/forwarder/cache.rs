@@ -0,0 +1,339 @@
+use crate::dns::proto::message::Message;
+use crate::dns::proto::question::Question;
+use crate::dns::proto::rcode::Rcode;
+use crate::dns::proto::record::Record;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// Upper bound on how long a response is kept, whatever its TTL
+const MAX_TTL: u32 = 86400;
+
+/// Upper bound for negative responses, as suggested by RFC 2308 section 5
+const MAX_NEGATIVE_TTL: u32 = 10800;
+
+/// Bookkeeping cost of a cached response on top of its wire size
+const SLOT_OVERHEAD: usize = 128;
+
+/// Question of a query, along with the flags that change the response upstream
+/// gives: DNSSEC records for DO, unvalidated data for CD
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    qname: String,
+    qtype: u16,
+    class: u16,
+    dnssec_ok: bool,
+    checking_disabled: bool,
+}
+
+#[derive(Debug)]
+struct Slot {
+    reply: Message,
+    stored: Instant,
+    expires: Instant,
+    size: usize,
+    /// Position in the recency order
+    used: u64,
+}
+
+/// Responses of upstream nameservers, keyed by question,
+/// evicting the least recently used ones past a memory cap
+#[derive(Debug)]
+pub struct Cache {
+    slots: HashMap<Key, Slot>,
+    /// Keys by last use, oldest first
+    recency: BTreeMap<u64, Key>,
+    capacity: usize,
+    size: usize,
+    tick: u64,
+}
+
+impl Key {
+    fn new(question: &Question, dnssec_ok: bool, checking_disabled: bool) -> Key {
+        Key {
+            qname: question.qname.fqdn().to_lowercase(),
+            qtype: question.qtype.to_num(),
+            class: question.class.to_num(),
+            dnssec_ok,
+            checking_disabled,
+        }
+    }
+
+    /// Key of a query, only single question ones are cached
+    fn of(msg: &Message) -> Option<Key> {
+        match &msg.questions()[..] {
+            [question] => Some(Key::new(question, msg.dnssec_ok(), msg.header().cd())),
+            _ => None,
+        }
+    }
+}
+
+impl Cache {
+    /// Cache holding up to `capacity` bytes of responses
+    pub fn new(capacity: usize) -> Cache {
+        Cache {
+            slots: HashMap::new(),
+            recency: BTreeMap::new(),
+            capacity,
+            size: 0,
+            tick: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Cached response to a query, with its ID, its question as asked
+    /// and TTLs counted down to `now`
+    pub fn get(&mut self, query: &Message, now: Instant) -> Option<Message> {
+        let key = Key::of(query)?;
+
+        if now >= self.slots.get(&key)?.expires {
+            self.remove(&key);
+            return None;
+        }
+
+        self.tick += 1;
+        let slot = self.slots.get_mut(&key)?;
+        self.recency.remove(&slot.used);
+        self.recency.insert(self.tick, key);
+        slot.used = self.tick;
+
+        let elapsed = now
+            .duration_since(slot.stored)
+            .as_secs()
+            .min(u32::MAX as u64) as u32;
+
+        let mut reply = slot.reply.clone();
+        reply.header_mut().set_id(query.header().id());
+        // clients randomizing the case of names check it is echoed back
+        *reply.questions_mut() = query.questions().clone();
+        for record in reply.records_mut() {
+            if let Some(ttl) = record.ttl() {
+                record.set_ttl(ttl.saturating_sub(elapsed));
+            }
+        }
+
+        Some(reply)
+    }
+
+    /// Stores a response for as long as its TTLs allow
+    pub fn put(&mut self, query: &Message, reply: &Message, now: Instant) {
+        let key = match Key::of(query) {
+            Some(key) => key,
+            None => return,
+        };
+        // a truncated response is going to be retried over TCP
+        if reply.header().tc() {
+            return;
+        }
+        let ttl = match Cache::ttl(reply) {
+            Some(ttl) if ttl > 0 => ttl,
+            _ => return,
+        };
+
+        let mut buf = vec![];
+        reply.write(&mut buf);
+        let size = buf.len() + key.qname.len() + SLOT_OVERHEAD;
+        if size > self.capacity {
+            return;
+        }
+
+        self.remove(&key);
+        while self.size + size > self.capacity {
+            let oldest = match self.recency.values().next() {
+                Some(oldest) => oldest.clone(),
+                None => break,
+            };
+            self.remove(&oldest);
+        }
+
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.size += size;
+        self.slots.insert(
+            key,
+            Slot {
+                reply: reply.clone(),
+                stored: now,
+                expires: now + Duration::from_secs(ttl as u64),
+                size,
+                used: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some(slot) = self.slots.remove(key) {
+            self.recency.remove(&slot.used);
+            self.size -= slot.size;
+        }
+    }
+
+    /// How long a response may be cached: the lowest TTL of its answers,
+    /// or for negative responses that of the SOA record in the authority section
+    fn ttl(reply: &Message) -> Option<u32> {
+        match reply.header().rcode() {
+            Rcode::NOERROR if !reply.answers().is_empty() => reply
+                .answers()
+                .iter()
+                .filter_map(|record| record.ttl())
+                .min()
+                .map(|ttl| ttl.min(MAX_TTL)),
+            Rcode::NOERROR | Rcode::NXDOMAIN => reply
+                .authority()
+                .iter()
+                .find_map(|record| match record {
+                    Record::SOA { ttl, minimum, .. } => Some((*ttl).min(*minimum)),
+                    _ => None,
+                })
+                .map(|ttl| ttl.min(MAX_NEGATIVE_TTL)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::proto::edns;
+    use crate::dns::proto::qclass::QClass;
+    use crate::dns::proto::qname::QName;
+    use crate::dns::proto::qtype::QType;
+    use std::net::Ipv4Addr;
+
+    fn query(qname: &str) -> Message {
+        let mut msg = Message::new();
+        msg.ask(Question::new(QName::from_str(qname), QType::A, None));
+        msg
+    }
+
+    fn reply(query: &Message, ttl: u32) -> Message {
+        let mut reply = Message::response_for(query);
+        reply.add_answer(Record::A {
+            qname: query.questions()[0].qname.clone(),
+            class: QClass::INTERNET,
+            ttl,
+            addr: Ipv4Addr::new(93, 184, 216, 34),
+        });
+        reply
+    }
+
+    fn soa(ttl: u32, minimum: u32) -> Record {
+        Record::SOA {
+            qname: QName::from_str("example.com."),
+            class: QClass::INTERNET,
+            ttl,
+            mname: QName::from_str("ns.example.com."),
+            rname: QName::from_str("hostmaster.example.com."),
+            serial: 1,
+            refresh: 7200,
+            retry: 3600,
+            expire: 1209600,
+            minimum,
+        }
+    }
+
+    #[test]
+    fn test_ttl_countdown() {
+        let mut cache = Cache::new(4096);
+        let now = Instant::now();
+        let msg = query("example.com.");
+        cache.put(&msg, &reply(&msg, 300), now);
+
+        let again = query("EXAMPLE.com.");
+        let cached = cache.get(&again, now + Duration::from_secs(100)).unwrap();
+        assert_eq!(again.header().id(), cached.header().id());
+        assert_eq!("EXAMPLE.com.", cached.questions()[0].qname.fqdn());
+        assert_eq!(Some(200), cached.answers()[0].ttl());
+
+        assert!(cache.get(&again, now + Duration::from_secs(300)).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_dnssec_flags() {
+        let mut cache = Cache::new(4096);
+        let now = Instant::now();
+        let msg = query("example.com.");
+        cache.put(&msg, &reply(&msg, 300), now);
+
+        let mut dnssec_ok = query("example.com.");
+        let mut opt = edns::opt(1232, 0);
+        if let Record::Option { dnssec_ok, .. } = &mut opt {
+            *dnssec_ok = true;
+        }
+        dnssec_ok.set_edns(Some(opt));
+        assert!(cache.get(&dnssec_ok, now).is_none());
+
+        let mut checking_disabled = query("example.com.");
+        checking_disabled.header_mut().set_cd(true);
+        assert!(cache.get(&checking_disabled, now).is_none());
+
+        assert!(cache.get(&query("example.com."), now).is_some());
+    }
+
+    #[test]
+    fn test_negative() {
+        let mut cache = Cache::new(4096);
+        let now = Instant::now();
+        let msg = query("missing.example.com.");
+
+        let mut nxdomain = Message::response_for(&msg);
+        nxdomain.set_rcode(Rcode::NXDOMAIN);
+        nxdomain.add_authority(soa(3600, 60));
+        cache.put(&msg, &nxdomain, now);
+
+        let cached = cache.get(&msg, now + Duration::from_secs(59)).unwrap();
+        assert_eq!(Rcode::NXDOMAIN, cached.header().rcode());
+        assert!(cache.get(&msg, now + Duration::from_secs(60)).is_none());
+
+        // without a SOA there is no telling how long the name stays missing
+        let mut nxdomain = Message::response_for(&msg);
+        nxdomain.set_rcode(Rcode::NXDOMAIN);
+        cache.put(&msg, &nxdomain, now);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_not_cached() {
+        let mut cache = Cache::new(4096);
+        let now = Instant::now();
+        let msg = query("example.com.");
+
+        let mut servfail = Message::response_for(&msg);
+        servfail.set_rcode(Rcode::SERVFAIL);
+        cache.put(&msg, &servfail, now);
+        cache.put(&msg, &reply(&msg, 0), now);
+
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let now = Instant::now();
+        let (a, b, c) = (
+            query("a.example."),
+            query("b.example."),
+            query("c.example."),
+        );
+
+        let mut buf = vec![];
+        reply(&a, 60).write(&mut buf);
+        let mut cache = Cache::new(2 * (buf.len() + "a.example.".len() + SLOT_OVERHEAD));
+
+        cache.put(&a, &reply(&a, 60), now);
+        cache.put(&b, &reply(&b, 60), now);
+        assert!(cache.get(&a, now).is_some());
+
+        cache.put(&c, &reply(&c, 60), now);
+        assert_eq!(2, cache.len());
+        assert!(cache.get(&b, now).is_none());
+        assert!(cache.get(&a, now).is_some());
+        assert!(cache.get(&c, now).is_some());
+    }
+}
@@ -0,0 +1,232 @@
+mod cache;
+
+pub use cache::Cache;
+
+use crate::dns::client::error::{ClientErr, ClientResult};
+use crate::dns::client::Nameserver;
+use crate::dns::cookie::ClientCookie;
+use crate::dns::proto::message::Message;
+use crate::dns::proto::rcode::Rcode;
+use rand::prelude::*;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Memory cap of the response cache, in bytes
+const DEFAULT_CACHE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Upstream nameserver along with our cookies with it
+struct Upstream {
+    nameserver: Nameserver,
+    cookie: Mutex<ClientCookie>,
+}
+
+impl Upstream {
+    /// Exchanges a query under our own cookie, once more if the server cookie
+    /// we sent has gone stale and upstream gave us a new one
+    fn exchange(&self, msg: &Message) -> ClientResult<Message> {
+        let reply = self.exchange_once(msg)?;
+        if reply.rcode() != Rcode::BADCOOKIE {
+            return Ok(reply);
+        }
+
+        self.exchange_once(msg)
+    }
+
+    /// Sends the query with an ID of our own, so that spoofing the response takes
+    /// guessing it, the client's ID is put back in the response
+    fn exchange_once(&self, msg: &Message) -> ClientResult<Message> {
+        let mut query = self.cookie.lock().unwrap().attach(msg);
+        query.header_mut().set_id(random());
+
+        let mut reply = self.nameserver.exchange(&query)?;
+        self.cookie.lock().unwrap().learn(&reply);
+        reply.header_mut().set_id(msg.header().id());
+
+        Ok(reply)
+    }
+}
+
+/// Relays queries we are not authoritative for to upstream nameservers,
+/// caching their responses
+pub struct Forwarder {
+    upstreams: Vec<Upstream>,
+    cache: Mutex<Cache>,
+}
+
+impl Forwarder {
+    pub fn new(upstreams: Vec<Nameserver>) -> Forwarder {
+        Forwarder {
+            upstreams: upstreams
+                .into_iter()
+                .map(|nameserver| Upstream {
+                    nameserver,
+                    cookie: Mutex::new(ClientCookie::new()),
+                })
+                .collect(),
+            cache: Mutex::new(Cache::new(DEFAULT_CACHE_SIZE)),
+        }
+    }
+
+    /// Sets the memory cap of the response cache, dropping what is cached so far
+    pub fn set_cache_size(&mut self, size: usize) {
+        self.cache = Mutex::new(Cache::new(size));
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.upstreams.is_empty()
+    }
+
+    /// Response of the first upstream that answers, with the ID of the query,
+    /// or SERVFAIL if none does, along with the errors of the upstreams that failed.
+    /// The COOKIE option of the query is replaced by ours,
+    /// the client's cookies are not for upstream to see.
+    pub fn forward(&self, msg: &Message) -> (Message, Vec<(SocketAddr, ClientErr)>) {
+        if let Some(reply) = self.cache.lock().unwrap().get(msg, Instant::now()) {
+            return (reply, vec![]);
+        }
+
+        let mut failures = vec![];
+        for upstream in self.upstreams.iter() {
+            match upstream.exchange(msg) {
+                Ok(reply) => {
+                    self.cache.lock().unwrap().put(msg, &reply, Instant::now());
+                    return (reply, failures);
+                }
+                Err(err) => failures.push((upstream.nameserver.addr(), err)),
+            }
+        }
+
+        let mut reply = Message::response_for(msg);
+        reply.set_rcode(Rcode::SERVFAIL);
+        (reply, failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::client::Protocol;
+    use crate::dns::proto::edns::{self, EdnsOption};
+    use crate::dns::proto::qname::QName;
+    use crate::dns::proto::qtype::QType;
+    use crate::dns::proto::question::Question;
+    use crate::dns::proto::record::Record;
+    use std::net::UdpSocket;
+
+    fn cookie(msg: &Message) -> Option<([u8; 8], Vec<u8>)> {
+        match msg.edns() {
+            Some(Record::Option { options, .. }) => {
+                options.iter().find_map(|option| match option {
+                    EdnsOption::Cookie { client, server } => Some((*client, server.clone())),
+                    _ => None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_no_upstreams() {
+        let mut msg = Message::new();
+        msg.ask(Question::new(
+            QName::from_str("example.com."),
+            QType::A,
+            None,
+        ));
+
+        let forwarder = Forwarder::new(vec![]);
+        let (reply, failures) = forwarder.forward(&msg);
+
+        assert!(!forwarder.is_enabled());
+        assert_eq!(msg.header().id(), reply.header().id());
+        assert_eq!(Rcode::SERVFAIL, reply.header().rcode());
+        assert_eq!(msg.questions(), reply.questions());
+        assert!(failures.is_empty());
+    }
+
+    #[test]
+    fn test_upstream_failures() {
+        // nothing listens on the port once the socket is gone
+        let addr = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let mut msg = Message::new();
+        msg.ask(Question::new(
+            QName::from_str("example.com."),
+            QType::A,
+            None,
+        ));
+
+        let forwarder = Forwarder::new(vec![Nameserver::new(addr, Protocol::UDP)]);
+        let (reply, failures) = forwarder.forward(&msg);
+
+        assert_eq!(Rcode::SERVFAIL, reply.rcode());
+        assert_eq!(1, failures.len());
+        assert_eq!(addr, failures[0].0);
+    }
+
+    #[test]
+    fn test_cookies_not_relayed() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let upstream = Nameserver::new(server.local_addr().unwrap(), Protocol::UDP);
+
+        let mut msg = Message::new();
+        msg.ask(Question::new(
+            QName::from_str("example.com."),
+            QType::A,
+            None,
+        ));
+        let mut opt = edns::opt(1232, 0);
+        if let Record::Option { options, .. } = &mut opt {
+            options.push(EdnsOption::Cookie {
+                client: [1; 8],
+                server: vec![2; 16],
+            });
+        }
+        msg.set_edns(Some(opt));
+
+        let responder = std::thread::spawn(move || {
+            let mut received = vec![];
+            for _ in 0..2 {
+                let mut buf = [0u8; 4096];
+                let (len, src) = server.recv_from(&mut buf).unwrap();
+                let query = Message::read(&buf[..len]).unwrap();
+                let (client, _) = cookie(&query).unwrap();
+
+                let mut reply = Message::response_for(&query);
+                let mut opt = edns::opt(1232, 0);
+                if let Record::Option { options, .. } = &mut opt {
+                    options.push(EdnsOption::Cookie {
+                        client,
+                        server: vec![3; 16],
+                    });
+                }
+                reply.set_edns(Some(opt));
+
+                let mut buf = vec![];
+                reply.write(&mut buf);
+                server.send_to(&buf, src).unwrap();
+                received.push(query);
+            }
+            received
+        });
+
+        let forwarder = Forwarder::new(vec![upstream]);
+        let (reply, failures) = forwarder.forward(&msg);
+        assert!(failures.is_empty());
+        forwarder.forward(&msg);
+        let received = responder.join().unwrap();
+
+        // upstream sees an ID of our own, the client gets its own back
+        assert_eq!(msg.header().id(), reply.header().id());
+
+        let (first, second) = (cookie(&received[0]), cookie(&received[1]));
+        assert_ne!([1; 8], first.as_ref().unwrap().0);
+        assert!(first.as_ref().unwrap().1.is_empty());
+        // the server cookie upstream gave us is sent back, along with our client cookie
+        assert_eq!(Some((first.unwrap().0, vec![3; 16])), second);
+    }
+}
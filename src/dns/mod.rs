@@ -1,3 +1,5 @@
 pub mod client;
+pub mod cookie;
+pub mod forwarder;
 pub mod proto;
 pub mod resolver;
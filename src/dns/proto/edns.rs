@@ -0,0 +1,348 @@
+use super::error::ProtoResult;
+use super::message::Message;
+use super::rcode::Rcode;
+use super::reader::Reader;
+use super::record::Record;
+use super::writer::Writer;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// EDNS version we implement, RFC 6891
+pub const EDNS_VERSION: u8 = 0;
+
+const NSID: u16 = 3;
+const CLIENT_SUBNET: u16 = 8;
+const COOKIE: u16 = 10;
+const PADDING: u16 = 12;
+
+const FAMILY_IPV4: u16 = 1;
+const FAMILY_IPV6: u16 = 2;
+
+/// Option carried in the RDATA of an OPT record
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdnsOption {
+    /// Name Server Identifier, RFC 5001, empty in queries
+    Nsid(Vec<u8>),
+    /// Client Subnet, RFC 7871, the address is zeroed past the source prefix
+    ClientSubnet {
+        addr: IpAddr,
+        source_prefix: u8,
+        scope_prefix: u8,
+    },
+    /// DNS Cookie, RFC 7873, the server cookie is empty in a first query
+    Cookie { client: [u8; 8], server: Vec<u8> },
+    /// Padding, RFC 7830, as the number of zero bytes
+    Padding(usize),
+    /// Option we do not implement, or a malformed one kept as is
+    Unknown { code: u16, data: Vec<u8> },
+}
+
+impl EdnsOption {
+    pub fn code(&self) -> u16 {
+        match self {
+            EdnsOption::Nsid(_) => NSID,
+            EdnsOption::ClientSubnet { .. } => CLIENT_SUBNET,
+            EdnsOption::Cookie { .. } => COOKIE,
+            EdnsOption::Padding(_) => PADDING,
+            EdnsOption::Unknown { code, .. } => *code,
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u16(self.code());
+
+        let data = match self {
+            EdnsOption::Nsid(data) => data.clone(),
+            EdnsOption::ClientSubnet {
+                addr,
+                source_prefix,
+                scope_prefix,
+            } => {
+                let (family, octets) = match addr {
+                    IpAddr::V4(addr) => (FAMILY_IPV4, addr.octets().to_vec()),
+                    IpAddr::V6(addr) => (FAMILY_IPV6, addr.octets().to_vec()),
+                };
+                // only the bytes covered by the source prefix are sent
+                let len = (*source_prefix as usize).div_ceil(8).min(octets.len());
+
+                let mut data = family.to_be_bytes().to_vec();
+                data.push(*source_prefix);
+                data.push(*scope_prefix);
+                data.extend_from_slice(&octets[..len]);
+                data
+            }
+            EdnsOption::Cookie { client, server } => {
+                let mut data = client.to_vec();
+                data.extend_from_slice(server);
+                data
+            }
+            EdnsOption::Padding(len) => vec![0; *len],
+            EdnsOption::Unknown { data, .. } => data.clone(),
+        };
+
+        writer.write_u16(data.len() as u16);
+        writer.write_vec(&data);
+    }
+
+    /// Reads an option, one that can not be decoded is kept as `Unknown`
+    /// so that it does not fail the whole message
+    pub fn read(reader: &mut Reader) -> ProtoResult<EdnsOption> {
+        let code = reader.read_u16()?;
+        let len = reader.read_u16()? as usize;
+        let data = reader.read_vec(len)?;
+
+        Ok(EdnsOption::decode(code, &data).unwrap_or(EdnsOption::Unknown { code, data }))
+    }
+
+    /// Option of a known code, None if its data is malformed
+    fn decode(code: u16, data: &[u8]) -> Option<EdnsOption> {
+        match code {
+            NSID => Some(EdnsOption::Nsid(data.to_vec())),
+            CLIENT_SUBNET => EdnsOption::client_subnet(data),
+            COOKIE => {
+                // client cookie alone, or with a server cookie of 8 to 32 bytes
+                if data.len() != 8 && !(16..=40).contains(&data.len()) {
+                    return None;
+                }
+
+                let mut client = [0u8; 8];
+                client.copy_from_slice(&data[..8]);
+                Some(EdnsOption::Cookie {
+                    client,
+                    server: data[8..].to_vec(),
+                })
+            }
+            PADDING => Some(EdnsOption::Padding(data.len())),
+            _ => None,
+        }
+    }
+
+    fn client_subnet(data: &[u8]) -> Option<EdnsOption> {
+        if data.len() < 4 {
+            return None;
+        }
+
+        let family = u16::from_be_bytes([data[0], data[1]]);
+        let (source_prefix, scope_prefix) = (data[2], data[3]);
+        let bytes = &data[4..];
+
+        let width = match family {
+            FAMILY_IPV4 => 4,
+            FAMILY_IPV6 => 16,
+            _ => return None,
+        };
+        if source_prefix as usize > width * 8 || bytes.len() != (source_prefix as usize).div_ceil(8)
+        {
+            return None;
+        }
+
+        let mut octets = [0u8; 16];
+        octets[..bytes.len()].copy_from_slice(bytes);
+
+        let addr = match family {
+            FAMILY_IPV4 => IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])),
+            _ => IpAddr::V6(Ipv6Addr::from(octets)),
+        };
+
+        Some(EdnsOption::ClientSubnet {
+            addr,
+            source_prefix,
+            scope_prefix,
+        })
+    }
+}
+
+/// OPT record advertising our payload size, without options
+pub fn opt(payload_size: u16, ext_rcode: u8) -> Record {
+    Record::Option {
+        payload_size,
+        ext_rcode,
+        version: EDNS_VERSION,
+        dnssec_ok: false,
+        options: vec![],
+    }
+}
+
+/// BADVERS response if the query asks for an EDNS version newer than ours, RFC 6891 6.1.3
+pub fn check_version(query: &Message, payload_size: u16) -> Option<Message> {
+    match query.edns() {
+        Some(Record::Option { version, .. }) if *version > EDNS_VERSION => {
+            let mut reply = Message::response_for(query);
+            reply.set_edns(Some(opt(payload_size, 0)));
+            reply.set_rcode(Rcode::BADVERS);
+            Some(reply)
+        }
+        _ => None,
+    }
+}
+
+/// Puts our own OPT record in a response if the query had one, and none otherwise.
+/// The extended RCODE of the response is kept, options are not echoed,
+/// the DO bit is copied from the query as RFC 3225 section 3 asks.
+pub fn echo(query: &Message, reply: &mut Message, payload_size: u16) {
+    let ext_rcode = match reply.edns() {
+        Some(Record::Option { ext_rcode, .. }) => *ext_rcode,
+        _ => 0,
+    };
+
+    match query.edns() {
+        Some(_) => {
+            let mut opt = opt(payload_size, ext_rcode);
+            if let Record::Option { dnssec_ok, .. } = &mut opt {
+                *dnssec_ok = query.dnssec_ok();
+            }
+            reply.set_edns(Some(opt));
+        }
+        None => reply.set_edns(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::proto::qclass::QClass;
+    use crate::dns::proto::qname::QName;
+    use crate::dns::proto::qtype::QType;
+    use crate::dns::proto::question::Question;
+
+    fn query(opt: Option<Record>) -> Message {
+        let mut msg = Message::new();
+        msg.ask(Question::new(QName::from_str("web.docker"), QType::A, None));
+        msg.set_edns(opt);
+        msg
+    }
+
+    fn roundtrip(option: EdnsOption) {
+        let mut buf = vec![];
+        option.write(&mut Writer::new(&mut buf));
+
+        let mut reader = Reader::new(&buf);
+        assert_eq!(option, EdnsOption::read(&mut reader).unwrap());
+        assert_eq!(0, reader.reminder());
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        roundtrip(EdnsOption::Nsid(b"dhns-1".to_vec()));
+        roundtrip(EdnsOption::ClientSubnet {
+            addr: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)),
+            source_prefix: 24,
+            scope_prefix: 0,
+        });
+        roundtrip(EdnsOption::ClientSubnet {
+            addr: "2001:db8::".parse().unwrap(),
+            source_prefix: 56,
+            scope_prefix: 48,
+        });
+        roundtrip(EdnsOption::Cookie {
+            client: [1, 2, 3, 4, 5, 6, 7, 8],
+            server: vec![],
+        });
+        roundtrip(EdnsOption::Cookie {
+            client: [1, 2, 3, 4, 5, 6, 7, 8],
+            server: vec![9; 16],
+        });
+        roundtrip(EdnsOption::Padding(12));
+        roundtrip(EdnsOption::Unknown {
+            code: 65001,
+            data: vec![0xCA, 0xFE],
+        });
+    }
+
+    #[test]
+    fn test_client_subnet_wire() {
+        let mut buf = vec![];
+        EdnsOption::ClientSubnet {
+            addr: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)),
+            source_prefix: 20,
+            scope_prefix: 0,
+        }
+        .write(&mut Writer::new(&mut buf));
+
+        assert_eq!(vec![0, 8, 0, 7, 0, 1, 20, 0, 192, 0, 2], buf);
+    }
+
+    #[test]
+    fn test_bad_options() {
+        // cookie with a 4 byte server part
+        let buf = [0, 10, 0, 12, 1, 2, 3, 4, 5, 6, 7, 8, 9, 9, 9, 9];
+        assert_eq!(
+            Ok(EdnsOption::Unknown {
+                code: COOKIE,
+                data: buf[4..].to_vec(),
+            }),
+            EdnsOption::read(&mut Reader::new(&buf))
+        );
+
+        // IPv4 subnet with more address bytes than the prefix covers
+        let buf = [0, 8, 0, 8, 0, 1, 8, 0, 10, 0, 0, 0];
+        assert_eq!(
+            Ok(EdnsOption::Unknown {
+                code: CLIENT_SUBNET,
+                data: buf[4..].to_vec(),
+            }),
+            EdnsOption::read(&mut Reader::new(&buf))
+        );
+
+        // the rest of the message is still read
+        let mut msg = query(Some(Record::Option {
+            payload_size: 1232,
+            ext_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: vec![EdnsOption::Unknown {
+                code: COOKIE,
+                data: vec![1, 2, 3],
+            }],
+        }));
+        msg.add_additional(Record::A {
+            qname: QName::from_str("web.docker"),
+            class: QClass::INTERNET,
+            ttl: 60,
+            addr: Ipv4Addr::new(192, 0, 2, 1),
+        });
+        let mut buf = vec![];
+        msg.write(&mut buf);
+        assert_eq!(2, Message::read(&buf).unwrap().additional().len());
+    }
+
+    #[test]
+    fn test_check_version() {
+        assert!(check_version(&query(None), 1232).is_none());
+        assert!(check_version(&query(Some(opt(512, 0))), 1232).is_none());
+
+        let mut newer = opt(512, 0);
+        if let Record::Option { version, .. } = &mut newer {
+            *version = 1;
+        }
+        let reply = check_version(&query(Some(newer)), 1232).unwrap();
+
+        assert_eq!(Rcode::BADVERS, reply.rcode());
+        assert_eq!(Rcode::NOERROR, reply.header().rcode());
+        assert!(reply.answers().is_empty());
+        assert_eq!(Some(&opt(1232, Rcode::BADVERS.ext_part())), reply.edns());
+    }
+
+    #[test]
+    fn test_echo() {
+        let mut reply = query(Some(Record::Option {
+            payload_size: 4096,
+            ext_rcode: 0,
+            version: 0,
+            dnssec_ok: true,
+            options: vec![EdnsOption::Nsid(b"upstream".to_vec())],
+        }));
+        echo(&query(Some(opt(512, 0))), &mut reply, 1232);
+        assert_eq!(Some(&opt(1232, 0)), reply.edns());
+
+        let mut dnssec_ok = opt(512, 0);
+        if let Record::Option { dnssec_ok, .. } = &mut dnssec_ok {
+            *dnssec_ok = true;
+        }
+        echo(&query(Some(dnssec_ok)), &mut reply, 1232);
+        assert!(reply.dnssec_ok());
+
+        echo(&query(None), &mut reply, 1232);
+        assert_eq!(None, reply.edns());
+        assert_eq!(512, reply.udp_payload_size());
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtoErr {
+    /// Message ended before a field could be read
+    Truncated,
+    /// Label has an unsupported type or is not valid UTF-8
+    BadLabel,
+    /// Label is longer than 63 bytes
+    LabelTooLong,
+    /// Name is longer than 255 bytes
+    NameTooLong,
+    /// Compression pointers never reach the end of a name
+    PointerLoop,
+    /// Compression pointer refers to a later part of the message
+    ForwardPointer,
+    /// RDLENGTH does not match the RDATA of the record
+    BadRdataLength,
+    /// Bytes left over after the last record
+    TrailingData,
+}
+
+pub type ProtoResult<T> = Result<T, ProtoErr>;
+
+impl fmt::Display for ProtoErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtoErr::Truncated => write!(f, "message is truncated"),
+            ProtoErr::BadLabel => write!(f, "invalid label"),
+            ProtoErr::LabelTooLong => write!(f, "label exceeds 63 bytes"),
+            ProtoErr::NameTooLong => write!(f, "name exceeds 255 bytes"),
+            ProtoErr::PointerLoop => write!(f, "compression pointer loop"),
+            ProtoErr::ForwardPointer => write!(f, "forward compression pointer"),
+            ProtoErr::BadRdataLength => write!(f, "RDLENGTH does not match RDATA"),
+            ProtoErr::TrailingData => write!(f, "message was not read completely"),
+        }
+    }
+}
+
+impl std::error::Error for ProtoErr {}
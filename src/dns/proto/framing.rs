@@ -0,0 +1,68 @@
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result};
+
+/// Reads one length-prefixed message of DNS over TCP, RFC 1035 section 4.2.2,
+/// `None` if the peer closed the connection in between messages
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 2];
+
+    // a closed connection is only expected before the length prefix
+    match reader.read(&mut len[..1])? {
+        0 => return Ok(None),
+        _ => reader.read_exact(&mut len[1..])?,
+    }
+
+    let mut buf = vec![0u8; u16::from_be_bytes(len) as usize];
+    reader.read_exact(&mut buf)?;
+
+    Ok(Some(buf))
+}
+
+/// Writes one message with its length prefix
+pub fn write_frame<W: Write>(writer: &mut W, msg: &[u8]) -> Result<()> {
+    if msg.len() > u16::MAX as usize {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "message too long for TCP framing",
+        ));
+    }
+
+    let mut buf = Vec::with_capacity(msg.len() + 2);
+    buf.extend_from_slice(&(msg.len() as u16).to_be_bytes());
+    buf.extend_from_slice(msg);
+
+    writer.write_all(&buf)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut buf = vec![];
+        write_frame(&mut buf, b"abc").unwrap();
+        write_frame(&mut buf, b"").unwrap();
+        assert_eq!(vec![0, 3, b'a', b'b', b'c', 0, 0], buf);
+
+        let mut reader = Cursor::new(buf);
+        assert_eq!(Some(b"abc".to_vec()), read_frame(&mut reader).unwrap());
+        assert_eq!(Some(vec![]), read_frame(&mut reader).unwrap());
+        assert_eq!(None, read_frame(&mut reader).unwrap());
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert!(read_frame(&mut Cursor::new(vec![0])).is_err());
+        assert!(read_frame(&mut Cursor::new(vec![0, 4, 1, 2])).is_err());
+    }
+
+    #[test]
+    fn test_too_long() {
+        let mut buf = vec![];
+        assert!(write_frame(&mut buf, &vec![0; 0x10000]).is_err());
+        assert!(buf.is_empty());
+    }
+}
@@ -1,9 +1,12 @@
 use rand::prelude::*;
 
+use super::error::ProtoResult;
+use super::opcode::Opcode;
+use super::rcode::Rcode;
 use super::reader::Reader;
 use super::writer::Writer;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Header {
     /// Packet Identifier
     /// A random identifier is assigned to query packets
@@ -40,6 +43,12 @@ pub struct Header {
     rcode: u8,
 }
 
+impl Default for Header {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Header {
     pub fn new() -> Header {
         Header {
@@ -55,31 +64,134 @@ impl Header {
         }
     }
 
+    /// Header for a response to this query:
+    /// keeps ID, opcode and RD bit, sets QR
+    pub fn response_to(query: &Header) -> Header {
+        Header {
+            id: query.id,
+            qr: 1,
+            opcode: query.opcode,
+            rd: query.rd,
+            ..Header::new()
+        }
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Whether this is a response
+    pub fn qr(&self) -> bool {
+        self.qr == 1
+    }
+
+    pub fn opcode(&self) -> Opcode {
+        Opcode::from_num(self.opcode)
+    }
+
+    pub fn aa(&self) -> bool {
+        self.aa == 1
+    }
+
+    pub fn tc(&self) -> bool {
+        self.tc == 1
+    }
+
+    pub fn rd(&self) -> bool {
+        self.rd == 1
+    }
+
+    pub fn ra(&self) -> bool {
+        self.ra == 1
+    }
+
+    pub fn z(&self) -> u8 {
+        self.z
+    }
+
+    /// Checking Disabled, the lowest of the Z bits since RFC 4035
+    pub fn cd(&self) -> bool {
+        self.z & 0x1 == 1
+    }
+
+    /// Response code as far as the header carries it,
+    /// extended codes also need the OPT record, see `Rcode::from_parts`
+    pub fn rcode(&self) -> Rcode {
+        Rcode::from_num(self.rcode as u16)
+    }
+
+    pub fn set_id(&mut self, id: u16) -> &mut Header {
+        self.id = id;
+        self
+    }
+
+    pub fn set_qr(&mut self, qr: bool) -> &mut Header {
+        self.qr = qr as u8;
+        self
+    }
+
+    pub fn set_opcode(&mut self, opcode: Opcode) -> &mut Header {
+        self.opcode = opcode.to_num() & 0xF;
+        self
+    }
+
+    pub fn set_aa(&mut self, aa: bool) -> &mut Header {
+        self.aa = aa as u8;
+        self
+    }
+
+    pub fn set_tc(&mut self, tc: bool) -> &mut Header {
+        self.tc = tc as u8;
+        self
+    }
+
+    pub fn set_rd(&mut self, rd: bool) -> &mut Header {
+        self.rd = rd as u8;
+        self
+    }
+
+    pub fn set_ra(&mut self, ra: bool) -> &mut Header {
+        self.ra = ra as u8;
+        self
+    }
+
+    pub fn set_cd(&mut self, cd: bool) -> &mut Header {
+        self.z = (self.z & !0x1) | cd as u8;
+        self
+    }
+
+    /// Sets the lower 4 bits of the response code,
+    /// use `Message::set_rcode` to set the upper bits in the OPT record too
+    pub(crate) fn set_rcode(&mut self, rcode: Rcode) -> &mut Header {
+        self.rcode = rcode.header_part();
+        self
+    }
+
     pub fn write(&self, writer: &mut Writer) {
         writer.write_u16(self.id);
 
         writer.write_u8(
-            (self.qr << 7) | (self.opcode << 3) | (self.aa << 2) | (self.tc << 1) | (self.rd << 0),
+            (self.qr << 7) | (self.opcode << 3) | (self.aa << 2) | (self.tc << 1) | self.rd,
         );
-        writer.write_u8((self.ra << 7) | (self.z << 4) | (self.rcode << 0));
+        writer.write_u8((self.ra << 7) | (self.z << 4) | self.rcode);
     }
 
-    pub fn read(reader: &mut Reader) -> Header {
-        let id = reader.read_u16();
+    pub fn read(reader: &mut Reader) -> ProtoResult<Header> {
+        let id = reader.read_u16()?;
 
-        let byte = reader.read_u8();
+        let byte = reader.read_u8()?;
         let qr = (byte & (0x1 << 7)) >> 7;
         let opcode = (byte & (0xF << 3)) >> 3;
         let aa = (byte & (0x1 << 2)) >> 2;
         let tc = (byte & (0x1 << 1)) >> 1;
-        let rd = (byte & (0x1 << 0)) >> 0;
+        let rd = byte & 0x1;
 
-        let byte = reader.read_u8();
+        let byte = reader.read_u8()?;
         let ra = (byte & (0x1 << 7)) >> 7;
         let z = (byte & (0x7 << 4)) >> 4;
-        let rcode = (byte & (0xF << 0)) >> 0;
+        let rcode = byte & 0xF;
 
-        Header {
+        Ok(Header {
             id,
             qr,
             opcode,
@@ -89,6 +201,48 @@ impl Header {
             ra,
             z,
             rcode,
-        }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut header = Header::new();
+        header
+            .set_opcode(Opcode::NOTIFY)
+            .set_qr(true)
+            .set_aa(true)
+            .set_rd(true)
+            .set_ra(true)
+            .set_cd(true)
+            .set_rcode(Rcode::NXDOMAIN);
+
+        let mut buf = vec![];
+        header.write(&mut Writer::new(&mut buf));
+        let read = Header::read(&mut Reader::new(&buf)).unwrap();
+
+        assert_eq!(header.id(), read.id());
+        assert!(read.qr() && read.aa() && read.rd() && read.ra() && read.cd());
+        assert!(!read.tc());
+        assert_eq!(Opcode::NOTIFY, read.opcode());
+        assert_eq!(Rcode::NXDOMAIN, read.rcode());
+    }
+
+    #[test]
+    fn test_response_to() {
+        let mut query = Header::new();
+        query.set_rd(true).set_tc(true);
+
+        let response = Header::response_to(&query);
+        assert_eq!(query.id(), response.id());
+        assert!(response.qr());
+        assert!(response.rd());
+        assert!(!response.tc());
+        assert_eq!(Opcode::QUERY, response.opcode());
+        assert_eq!(Rcode::NOERROR, response.rcode());
     }
 }
@@ -1,10 +1,18 @@
+use super::edns;
+use super::error::{ProtoErr, ProtoResult};
 use super::header::Header;
+use super::qname::QName;
+use super::qtype::QType;
 use super::question::Question;
+use super::rcode::Rcode;
 use crate::dns::proto::reader::Reader;
 use crate::dns::proto::record::Record;
 use crate::dns::proto::writer::Writer;
 
-#[derive(Debug)]
+/// Payload every client accepts over UDP, RFC 1035 section 4.2.1
+pub const UDP_PAYLOAD_MIN: usize = 512;
+
+#[derive(Debug, Clone)]
 pub struct Message {
     header: Header,
     questions: Vec<Question>,
@@ -13,6 +21,12 @@ pub struct Message {
     additional: Vec<Record>,
 }
 
+impl Default for Message {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Message {
     pub fn questions(&self) -> &Vec<Question> {
         &self.questions
@@ -28,10 +42,205 @@ impl Message {
         }
     }
 
+    pub fn questions_mut(&mut self) -> &mut Vec<Question> {
+        &mut self.questions
+    }
+
     pub fn ask(&mut self, question: Question) {
         self.questions.push(question);
     }
 
+    /// Empty response to a query, with its ID, opcode and RD bit
+    /// and the questions copied over
+    pub fn response_for(query: &Message) -> Message {
+        Message {
+            header: Header::response_to(&query.header),
+            questions: query.questions.clone(),
+            ..Message::new()
+        }
+    }
+
+    /// Whether this is a response to `query`: QR set, same ID and the same questions,
+    /// names compared ignoring case
+    pub fn is_response_to(&self, query: &Message) -> bool {
+        self.header.qr()
+            && self.header.id() == query.header.id()
+            && self.questions.len() == query.questions.len()
+            && self
+                .questions
+                .iter()
+                .zip(query.questions.iter())
+                .all(|(ours, theirs)| {
+                    ours.qname.matches(&theirs.qname)
+                        && ours.qtype == theirs.qtype
+                        && ours.class == theirs.class
+                })
+    }
+
+    /// FORMERR response to a query that could not be read,
+    /// None unless at least its header could and it is not a response itself
+    pub fn format_error(buffer: &[u8]) -> Option<Message> {
+        let header = Header::read(&mut Reader::new(buffer)).ok()?;
+        if header.qr() {
+            return None;
+        }
+
+        let mut reply = Message {
+            header: Header::response_to(&header),
+            ..Message::new()
+        };
+        reply.set_rcode(Rcode::FORMERR);
+        Some(reply)
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    pub fn answers(&self) -> &Vec<Record> {
+        &self.answers
+    }
+
+    pub fn answers_mut(&mut self) -> &mut Vec<Record> {
+        &mut self.answers
+    }
+
+    pub fn add_answer(&mut self, record: Record) {
+        self.answers.push(record);
+    }
+
+    pub fn authority(&self) -> &Vec<Record> {
+        &self.authority
+    }
+
+    pub fn authority_mut(&mut self) -> &mut Vec<Record> {
+        &mut self.authority
+    }
+
+    pub fn add_authority(&mut self, record: Record) {
+        self.authority.push(record);
+    }
+
+    pub fn additional(&self) -> &Vec<Record> {
+        &self.additional
+    }
+
+    pub fn additional_mut(&mut self) -> &mut Vec<Record> {
+        &mut self.additional
+    }
+
+    pub fn add_additional(&mut self, record: Record) {
+        self.additional.push(record);
+    }
+
+    /// Response code, including the extended bits of the OPT record
+    pub fn rcode(&self) -> Rcode {
+        match self.edns() {
+            Some(Record::Option { ext_rcode, .. }) => {
+                Rcode::from_parts(self.header.rcode().header_part(), *ext_rcode)
+            }
+            _ => self.header.rcode(),
+        }
+    }
+
+    /// Sets the response code in the header and the OPT record.
+    /// Extended codes need an OPT record, a minimal one is added if there is none.
+    pub fn set_rcode(&mut self, rcode: Rcode) {
+        self.header.set_rcode(rcode);
+        if rcode.ext_part() != 0 && self.edns().is_none() {
+            self.set_edns(Some(edns::opt(UDP_PAYLOAD_MIN as u16, 0)));
+        }
+        for record in self.additional.iter_mut() {
+            if let Record::Option { ext_rcode, .. } = record {
+                *ext_rcode = rcode.ext_part();
+            }
+        }
+    }
+
+    /// Answers of the given type, whatever their name
+    pub fn answers_of<'a>(&'a self, qtype: &'a QType) -> impl Iterator<Item = &'a Record> + 'a {
+        self.answers
+            .iter()
+            .filter(move |record| record.qtype() == *qtype)
+    }
+
+    /// Name the answers for `qname` are found under, following CNAME records
+    pub fn canonical_name<'a>(&'a self, qname: &'a QName) -> &'a QName {
+        let mut name = qname;
+
+        // a chain can't be longer than the answer section, unless it loops
+        for _ in 0..self.answers.len() {
+            let cname = self.answers.iter().find_map(|record| match record {
+                Record::CNAME { qname, cname, .. } if qname.matches(name) => Some(cname),
+                _ => None,
+            });
+
+            match cname {
+                Some(cname) => name = cname,
+                None => break,
+            }
+        }
+
+        name
+    }
+
+    /// Answers of the given type for `qname`, at the end of its CNAME chain.
+    /// CNAME records are those of `qname` itself, not of the end of the chain.
+    pub fn answers_for<'a>(&'a self, qname: &'a QName, qtype: &'a QType) -> Vec<&'a Record> {
+        let name = match qtype {
+            QType::CNAME => qname,
+            _ => self.canonical_name(qname),
+        };
+
+        self.answers_of(qtype)
+            .filter(|record| record.qname().is_some_and(|owner| owner.matches(name)))
+            .collect()
+    }
+
+    /// OPT pseudo-record, if the sender supports EDNS
+    pub fn edns(&self) -> Option<&Record> {
+        self.additional
+            .iter()
+            .find(|record| matches!(record, Record::Option { .. }))
+    }
+
+    /// Replaces the OPT pseudo-record, `None` removes it
+    pub fn set_edns(&mut self, opt: Option<Record>) {
+        self.additional
+            .retain(|record| !matches!(record, Record::Option { .. }));
+        self.additional.extend(opt);
+    }
+
+    /// Whether the sender wants DNSSEC records, the DO bit of RFC 3225
+    pub fn dnssec_ok(&self) -> bool {
+        match self.edns() {
+            Some(Record::Option { dnssec_ok, .. }) => *dnssec_ok,
+            _ => false,
+        }
+    }
+
+    /// Largest UDP response the sender accepts, as advertised with EDNS
+    pub fn udp_payload_size(&self) -> usize {
+        match self.edns() {
+            Some(Record::Option { payload_size, .. }) => {
+                (*payload_size as usize).max(UDP_PAYLOAD_MIN)
+            }
+            _ => UDP_PAYLOAD_MIN,
+        }
+    }
+
+    /// Records of all sections
+    pub fn records_mut(&mut self) -> impl Iterator<Item = &mut Record> {
+        self.answers
+            .iter_mut()
+            .chain(self.authority.iter_mut())
+            .chain(self.additional.iter_mut())
+    }
+
     pub fn write(&self, buf: &mut Vec<u8>) {
         let mut writer = Writer::new(buf);
 
@@ -59,42 +268,83 @@ impl Message {
         }
     }
 
-    pub fn read(buffer: &Vec<u8>) -> Result<Message, &'static str> {
+    /// Writes the message in at most `max` bytes where possible, dropping whole RRsets
+    /// from the end: additional records first, setting TC once answers have to go
+    pub fn write_within(&self, buf: &mut Vec<u8>, max: usize) {
+        let start = buf.len();
+        self.write(buf);
+        if buf.len() - start <= max {
+            return;
+        }
+
+        let mut msg = self.clone();
+        while msg.drop_rrset() {
+            buf.truncate(start);
+            msg.write(buf);
+            if buf.len() - start <= max {
+                return;
+            }
+        }
+    }
+
+    /// Removes the last RRset, `false` if there is nothing left to drop
+    fn drop_rrset(&mut self) -> bool {
+        // OPT is not an RRset and tells the client about our EDNS support
+        let opt = self
+            .additional
+            .iter()
+            .position(|record| matches!(record, Record::Option { .. }))
+            .map(|pos| self.additional.remove(pos));
+        let dropped = pop_rrset(&mut self.additional);
+        self.additional.extend(opt);
+        if dropped {
+            return true;
+        }
+
+        if pop_rrset(&mut self.authority) || pop_rrset(&mut self.answers) {
+            self.header.set_tc(true);
+            return true;
+        }
+
+        false
+    }
+
+    pub fn read(buffer: &[u8]) -> ProtoResult<Message> {
         let mut reader = Reader::new(buffer);
 
-        let header = Header::read(&mut reader);
+        let header = Header::read(&mut reader)?;
 
-        let mut qdcount = reader.read_u16();
-        let mut ancount = reader.read_u16();
-        let mut nscount = reader.read_u16();
-        let mut arcount = reader.read_u16();
+        let mut qdcount = reader.read_u16()?;
+        let mut ancount = reader.read_u16()?;
+        let mut nscount = reader.read_u16()?;
+        let mut arcount = reader.read_u16()?;
 
         let mut questions: Vec<Question> = vec![];
         while qdcount != 0 {
-            questions.push(Question::read(&mut reader));
-            qdcount = qdcount - 1;
+            questions.push(Question::read(&mut reader)?);
+            qdcount -= 1;
         }
 
         let mut answers: Vec<Record> = vec![];
         while ancount != 0 {
-            answers.push(Record::read(&mut reader));
-            ancount = ancount - 1;
+            answers.push(Record::read(&mut reader)?);
+            ancount -= 1;
         }
 
         let mut authority = vec![];
         while nscount != 0 {
-            authority.push(Record::read(&mut reader));
-            nscount = nscount - 1;
+            authority.push(Record::read(&mut reader)?);
+            nscount -= 1;
         }
 
         let mut additional: Vec<Record> = vec![];
         while arcount != 0 {
-            additional.push(Record::read(&mut reader));
-            arcount = arcount - 1;
+            additional.push(Record::read(&mut reader)?);
+            arcount -= 1;
         }
 
         if reader.reminder() > 0 {
-            return Err("Message was not read completely");
+            return Err(ProtoErr::TrailingData);
         }
 
         Ok(Message {
@@ -106,3 +356,238 @@ impl Message {
         })
     }
 }
+
+/// Removes the trailing records that belong to the same RRset
+fn pop_rrset(records: &mut Vec<Record>) -> bool {
+    let last = match records.pop() {
+        Some(last) => last,
+        None => return false,
+    };
+
+    while records
+        .last()
+        .is_some_and(|record| record.same_rrset(&last))
+    {
+        records.pop();
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::proto::qclass::QClass;
+    use crate::dns::proto::qname::QName;
+    use crate::dns::proto::qtype::QType;
+    use std::net::Ipv4Addr;
+
+    fn a(qname: &str, last: u8) -> Record {
+        Record::A {
+            qname: QName::from_str(qname),
+            class: QClass::INTERNET,
+            ttl: 10,
+            addr: Ipv4Addr::new(172, 17, 0, last),
+        }
+    }
+
+    fn opt(payload_size: u16) -> Record {
+        Record::Option {
+            payload_size,
+            ext_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: vec![],
+        }
+    }
+
+    fn reply(answers: Vec<Record>) -> Message {
+        let mut msg = Message::new();
+        msg.ask(Question::new(
+            QName::from_str("web.docker."),
+            QType::A,
+            None,
+        ));
+
+        let mut reply = Message::response_for(&msg);
+        for answer in answers {
+            reply.add_answer(answer);
+        }
+        reply
+    }
+
+    #[test]
+    fn test_write_within_fits() {
+        let reply = reply(vec![a("web.docker.", 2)]);
+
+        let mut buf = vec![];
+        reply.write_within(&mut buf, UDP_PAYLOAD_MIN);
+
+        let read = Message::read(&buf).unwrap();
+        assert!(!read.header().tc());
+        assert_eq!(1, read.answers().len());
+    }
+
+    #[test]
+    fn test_write_within_drops_rrsets() {
+        let mut answers: Vec<Record> = (0..40).map(|n| a("web.docker.", n)).collect();
+        answers.push(a("db.docker.", 100));
+        let mut reply = reply(answers);
+        reply.additional.push(a("extra.docker.", 200));
+        reply.set_edns(Some(opt(512)));
+
+        let mut buf = vec![];
+        reply.write_within(&mut buf, 200);
+        assert!(buf.len() <= 200);
+
+        // the 40 record RRset doesn't fit on its own, so it goes as a whole
+        let read = Message::read(&buf).unwrap();
+        assert!(read.header().tc());
+        assert!(read.answers().is_empty());
+        assert_eq!(1, read.additional().len());
+        assert_eq!(QType::OPTION, read.additional()[0].qtype());
+
+        // additional records go first, without setting TC
+        let mut buf = vec![];
+        reply.write_within(&mut buf, 700);
+
+        let read = Message::read(&buf).unwrap();
+        assert!(!read.header().tc());
+        assert_eq!(41, read.answers().len());
+        assert_eq!(1, read.additional().len());
+    }
+
+    #[test]
+    fn test_udp_payload_size() {
+        let mut msg = Message::new();
+        assert_eq!(UDP_PAYLOAD_MIN, msg.udp_payload_size());
+
+        msg.set_edns(Some(opt(1232)));
+        assert_eq!(1232, msg.udp_payload_size());
+    }
+
+    fn cname(qname: &str, cname: &str) -> Record {
+        Record::CNAME {
+            qname: QName::from_str(qname),
+            class: QClass::INTERNET,
+            ttl: 10,
+            cname: QName::from_str(cname),
+        }
+    }
+
+    #[test]
+    fn test_answers_for() {
+        let reply = reply(vec![
+            cname("web.docker.", "Front.docker."),
+            cname("front.docker.", "nginx.docker."),
+            a("nginx.docker.", 2),
+            a("nginx.docker.", 3),
+            a("db.docker.", 4),
+        ]);
+        let qname = QName::from_str("web.docker.");
+
+        assert_eq!(2, reply.answers_of(&QType::CNAME).count());
+        assert_eq!(
+            &QName::from_str("nginx.docker."),
+            reply.canonical_name(&qname)
+        );
+        assert_eq!(
+            vec![&reply.answers()[2], &reply.answers()[3]],
+            reply.answers_for(&qname, &QType::A)
+        );
+        assert_eq!(
+            vec![&reply.answers()[0]],
+            reply.answers_for(&qname, &QType::CNAME)
+        );
+    }
+
+    #[test]
+    fn test_cname_loop() {
+        let reply = reply(vec![
+            cname("web.docker.", "front.docker."),
+            cname("front.docker.", "web.docker."),
+        ]);
+        let qname = QName::from_str("web.docker.");
+
+        assert!(reply.answers_for(&qname, &QType::A).is_empty());
+    }
+
+    #[test]
+    fn test_is_response_to() {
+        let mut query = Message::new();
+        query.ask(Question::new(
+            QName::from_str("Web.Docker."),
+            QType::A,
+            None,
+        ));
+
+        let reply = Message::response_for(&query);
+        assert!(reply.is_response_to(&query));
+        assert!(!query.is_response_to(&query));
+
+        let mut other = reply.clone();
+        other
+            .header_mut()
+            .set_id(query.header().id().wrapping_add(1));
+        assert!(!other.is_response_to(&query));
+
+        let mut other = reply.clone();
+        other.questions_mut()[0].qtype = QType::AAAA;
+        assert!(!other.is_response_to(&query));
+
+        let mut other = reply;
+        other.questions_mut()[0].qname = QName::from_str("web.docker.local.");
+        assert!(!other.is_response_to(&query));
+        other.questions_mut()[0].qname = QName::from_str("web.docker");
+        assert!(other.is_response_to(&query));
+    }
+
+    #[test]
+    fn test_format_error() {
+        let mut query = Message::new();
+        query.ask(Question::new(
+            QName::from_str("web.docker."),
+            QType::A,
+            None,
+        ));
+        let mut buf = vec![];
+        query.write(&mut buf);
+        buf.truncate(buf.len() - 1);
+        assert!(Message::read(&buf).is_err());
+
+        let reply = Message::format_error(&buf).unwrap();
+        assert_eq!(query.header().id(), reply.header().id());
+        assert_eq!(Rcode::FORMERR, reply.rcode());
+        assert!(reply.questions().is_empty());
+
+        // no header, or a response, is not answered
+        assert!(Message::format_error(&buf[..3]).is_none());
+        let mut buf = vec![];
+        Message::response_for(&query).write(&mut buf);
+        assert!(Message::format_error(&buf).is_none());
+    }
+
+    #[test]
+    fn test_extended_rcode() {
+        let mut reply = reply(vec![]);
+        reply.set_edns(Some(opt(1232)));
+        reply.set_rcode(Rcode::BADCOOKIE);
+
+        let mut buf = vec![];
+        reply.write(&mut buf);
+        let read = Message::read(&buf).unwrap();
+
+        assert_eq!(Rcode::BADCOOKIE, read.rcode());
+        assert_eq!(Rcode::YXRRSET, read.header().rcode());
+
+        reply.set_rcode(Rcode::NXDOMAIN);
+        assert_eq!(Rcode::NXDOMAIN, reply.rcode());
+
+        // extended codes can't go without an OPT record
+        let mut reply = Message::response_for(&reply);
+        reply.set_rcode(Rcode::BADVERS);
+        assert_eq!(Rcode::BADVERS, reply.rcode());
+        assert_eq!(512, reply.udp_payload_size());
+        assert!(reply.edns().is_some());
+    }
+}
@@ -1,9 +1,14 @@
+pub mod edns;
+pub mod error;
+pub mod framing;
 pub mod header;
 pub mod message;
+pub mod opcode;
 pub mod qclass;
 pub mod qname;
 pub mod qtype;
 pub mod question;
+pub mod rcode;
 pub mod reader;
 pub mod record;
 pub mod writer;
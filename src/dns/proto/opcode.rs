@@ -0,0 +1,34 @@
+/// Kind of query, set by the originator and copied into the response
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Opcode {
+    UNKNOWN(u8),
+    QUERY,
+    IQUERY,
+    STATUS,
+    NOTIFY,
+    UPDATE,
+}
+
+impl Opcode {
+    pub fn from_num(num: u8) -> Opcode {
+        match num {
+            0 => Opcode::QUERY,
+            1 => Opcode::IQUERY,
+            2 => Opcode::STATUS,
+            4 => Opcode::NOTIFY,
+            5 => Opcode::UPDATE,
+            _ => Opcode::UNKNOWN(num),
+        }
+    }
+
+    pub fn to_num(&self) -> u8 {
+        match self {
+            Opcode::QUERY => 0,
+            Opcode::IQUERY => 1,
+            Opcode::STATUS => 2,
+            Opcode::NOTIFY => 4,
+            Opcode::UPDATE => 5,
+            Opcode::UNKNOWN(x) => *x,
+        }
+    }
+}
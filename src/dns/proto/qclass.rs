@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum QClass {
     UNKNOWN(u16),
     INTERNET,
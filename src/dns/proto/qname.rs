@@ -1,5 +1,18 @@
+use crate::dns::proto::error::{ProtoErr, ProtoResult};
 use crate::dns::proto::reader::Reader;
+use crate::dns::proto::writer::Writer;
 
+/// A name can't have more labels than this,
+/// so following more pointers means we are going in circles
+const MAX_POINTERS: usize = 127;
+
+/// Labels are limited to 63 bytes on the wire (RFC 1035 2.3.4)
+const MAX_LABEL_LEN: usize = 63;
+
+/// Names are limited to 255 bytes on the wire (RFC 1035 2.3.4)
+const MAX_NAME_LEN: usize = 255;
+
+#[derive(Clone, PartialEq)]
 pub struct QName {
     labels: Vec<String>,
 }
@@ -13,31 +26,131 @@ impl QName {
         &self.labels
     }
 
+    /// Same name, ignoring case and the root label
+    pub fn matches(&self, other: &QName) -> bool {
+        let mut ours = self.labels.iter().filter(|label| !label.is_empty());
+        let mut theirs = other.labels.iter().filter(|label| !label.is_empty());
+
+        loop {
+            match (ours.next(), theirs.next()) {
+                (Some(our), Some(their)) if our.eq_ignore_ascii_case(their) => continue,
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Whether the name fits on the wire: no label over 63 bytes, 255 bytes in all
+    pub fn is_valid(&self) -> bool {
+        let mut labels = self.labels.iter().filter(|l| !l.is_empty());
+        // wire length, including the root label
+        let name_len: usize = 1 + labels.clone().map(|l| 1 + l.len()).sum::<usize>();
+
+        name_len <= MAX_NAME_LEN && labels.all(|l| l.len() <= MAX_LABEL_LEN)
+    }
+
     pub fn fqdn(&self) -> String {
         self.labels.join(".")
     }
 
-    pub fn read(reader: &mut Reader) -> QName {
+    /// Writes name, replacing any suffix already present in the message
+    /// with a compression pointer. A name that does not fit on the wire
+    /// is written as the root name, check `is_valid` beforehand.
+    pub fn write(&self, writer: &mut Writer) {
+        if !self.is_valid() {
+            writer.write_u8(0);
+            return;
+        }
+
+        let labels: Vec<&String> = self.labels.iter().filter(|l| !l.is_empty()).collect();
+
+        for (idx, label) in labels.iter().enumerate() {
+            let suffix = labels[idx..]
+                .iter()
+                .map(|l| l.as_str())
+                .collect::<Vec<&str>>()
+                .join(".");
+
+            if let Some(offset) = writer.find_name(&suffix) {
+                writer.write_u16(0xC000 | offset);
+                return;
+            }
+
+            writer.add_name(&suffix);
+
+            let bytes = label.as_bytes();
+            writer.write_u8(bytes.len() as u8);
+            writer.write_vec(bytes);
+        }
+
+        writer.write_u8(0);
+    }
+
+    /// Writes name in full, for RDATA where compression is not allowed,
+    /// the root name if it does not fit on the wire
+    pub fn write_uncompressed(&self, writer: &mut Writer) {
+        if !self.is_valid() {
+            writer.write_u8(0);
+            return;
+        }
+
+        for label in self.labels.iter().filter(|l| !l.is_empty()) {
+            let bytes = label.as_bytes();
+            writer.write_u8(bytes.len() as u8);
+            writer.write_vec(bytes);
+        }
+
+        writer.write_u8(0);
+    }
+
+    pub fn read(reader: &mut Reader) -> ProtoResult<QName> {
         let mut labels: Vec<String> = vec![];
         let mut retpos = 0usize;
+        let mut jumps = 0usize;
+        // pointers may only go back past everything read so far,
+        // which guarantees we never visit the same byte twice
+        let mut lowest = reader.pos();
+        // wire length of the name, including the root label
+        let mut name_len = 1usize;
 
         loop {
-            let len = reader.read_u8() as usize;
+            let pos = reader.pos();
+            let len = reader.read_u8()? as usize;
 
             if len == 0 {
                 break;
             }
 
-            if 0b11 == (len >> 6) {
-                let offset = ((len - 192) << 8) | (reader.read_u8() as usize);
-                let oldpos = reader.seek(offset);
-                if 0 == retpos {
-                    // store position of buffer on first pointer seek
-                    retpos = oldpos;
+            match len >> 6 {
+                0b11 => {
+                    let offset = ((len & 0x3F) << 8) | (reader.read_u8()? as usize);
+
+                    if offset > pos {
+                        return Err(ProtoErr::ForwardPointer);
+                    }
+
+                    jumps += 1;
+                    if offset >= lowest || jumps > MAX_POINTERS {
+                        return Err(ProtoErr::PointerLoop);
+                    }
+
+                    lowest = offset;
+                    let oldpos = reader.seek(offset);
+                    if 0 == retpos {
+                        // store position of buffer on first pointer seek
+                        retpos = oldpos;
+                    }
                 }
-                continue;
-            } else {
-                labels.push(reader.read_str(len));
+                0b00 => {
+                    name_len += 1 + len;
+                    if name_len > MAX_NAME_LEN {
+                        return Err(ProtoErr::NameTooLong);
+                    }
+
+                    labels.push(reader.read_str(len)?);
+                }
+                0b01 => return Err(ProtoErr::LabelTooLong),
+                _ => return Err(ProtoErr::BadLabel),
             }
         }
 
@@ -46,15 +159,13 @@ impl QName {
             reader.seek(retpos);
         }
 
-        QName { labels }
+        Ok(QName { labels })
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(string: &str) -> QName {
         QName {
-            labels: String::from(string)
-                .split(".")
-                .map(|s| String::from(s))
-                .collect(),
+            labels: String::from(string).split('.').map(String::from).collect(),
         }
     }
 }
@@ -64,3 +175,161 @@ impl std::fmt::Debug for QName {
         write!(f, "{}", self.fqdn())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let name = QName::from_str("Web.Docker.");
+
+        assert!(name.matches(&QName::from_str("web.docker")));
+        assert!(name.matches(&QName::from_str("WEB.DOCKER.")));
+        assert!(!name.matches(&QName::from_str("web.docker.local.")));
+        assert!(!name.matches(&QName::from_str("docker.")));
+        assert!(!name.matches(&QName::from_str("wab.docker.")));
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let label = "a".repeat(MAX_LABEL_LEN);
+        assert!(QName::from_str(&format!("{}.docker.", label)).is_valid());
+        assert!(!QName::from_str(&format!("a{}.docker.", label)).is_valid());
+
+        // 4 labels of 63 bytes take 256 bytes with their lengths and the root
+        let name = [label.as_str(); 4].join(".");
+        assert!(!QName::from_str(&name).is_valid());
+        assert!(QName::from_str(&name[2..]).is_valid());
+    }
+
+    #[test]
+    fn test_write_invalid() {
+        let mut buf: Vec<u8> = vec![];
+        let mut writer = Writer::new(&mut buf);
+
+        let name = QName::from_str(&format!("{}.docker.", "a".repeat(64)));
+        name.write(&mut writer);
+        name.write_uncompressed(&mut writer);
+
+        assert_eq!(vec![0, 0], buf);
+    }
+
+    #[test]
+    fn test_write_compressed() {
+        let mut buf: Vec<u8> = vec![];
+        let mut writer = Writer::new(&mut buf);
+
+        QName::from_str("web.docker.").write(&mut writer);
+        QName::from_str("db.docker.").write(&mut writer);
+        QName::from_str("WEB.docker.").write(&mut writer);
+
+        assert_eq!(
+            vec![
+                3, b'w', b'e', b'b', 6, b'd', b'o', b'c', b'k', b'e', b'r', 0, // web.docker
+                2, b'd', b'b', 0xC0, 4, // db + pointer to docker
+                0xC0, 0, // pointer to web.docker
+            ],
+            buf
+        );
+
+        let mut reader = Reader::new(&buf);
+        assert_eq!("web.docker", QName::read(&mut reader).unwrap().fqdn());
+        assert_eq!("db.docker", QName::read(&mut reader).unwrap().fqdn());
+        assert_eq!("web.docker", QName::read(&mut reader).unwrap().fqdn());
+        assert_eq!(0, reader.reminder());
+    }
+
+    #[test]
+    fn test_read_pointer_loop() {
+        let buf = vec![3, b'w', b'e', b'b', 0xC0, 0];
+        let mut reader = Reader::new(&buf);
+
+        assert_eq!(Err(ProtoErr::PointerLoop), QName::read(&mut reader));
+    }
+
+    #[test]
+    fn test_read_pointer_to_itself() {
+        let buf = vec![0, 0xC0, 1];
+        let mut reader = Reader::new(&buf);
+        reader.seek(1);
+
+        assert_eq!(Err(ProtoErr::PointerLoop), QName::read(&mut reader));
+    }
+
+    #[test]
+    fn test_read_pointer_cycle_goes_forward() {
+        // `b` at 0 points to `a` at 4, which points back to `b`:
+        // closing the cycle takes a pointer ahead of itself
+        let buf = vec![1, b'b', 0xC0, 4, 1, b'a', 0xC0, 0];
+        let mut reader = Reader::new(&buf);
+        reader.seek(4);
+
+        assert_eq!(Err(ProtoErr::ForwardPointer), QName::read(&mut reader));
+    }
+
+    #[test]
+    fn test_read_pointer_chain_too_long() {
+        // root label at 0, then pointers each pointing at the one before
+        let mut buf = vec![0, 0xC0, 0];
+        for n in 1..200u16 {
+            buf.extend_from_slice(&(0xC000 | (2 * n - 1)).to_be_bytes());
+        }
+        let mut reader = Reader::new(&buf);
+        reader.seek(buf.len() - 2);
+
+        assert_eq!(Err(ProtoErr::PointerLoop), QName::read(&mut reader));
+
+        // a short chain is fine
+        let mut reader = Reader::new(&buf);
+        reader.seek(7);
+        assert_eq!(Ok(QName::new(vec![])), QName::read(&mut reader));
+    }
+
+    #[test]
+    fn test_read_forward_pointer() {
+        let buf = vec![0xC0, 2, 3, b'w', b'e', b'b', 0];
+        let mut reader = Reader::new(&buf);
+
+        assert_eq!(Err(ProtoErr::ForwardPointer), QName::read(&mut reader));
+    }
+
+    #[test]
+    fn test_read_label_too_long() {
+        let mut buf = vec![64u8];
+        buf.extend_from_slice(&[b'a'; 64]);
+        buf.push(0);
+        let mut reader = Reader::new(&buf);
+
+        assert_eq!(Err(ProtoErr::LabelTooLong), QName::read(&mut reader));
+    }
+
+    #[test]
+    fn test_read_name_too_long() {
+        let mut buf = vec![];
+        for _ in 0..5 {
+            buf.push(63u8);
+            buf.extend_from_slice(&[b'a'; 63]);
+        }
+        buf.push(0);
+        let mut reader = Reader::new(&buf);
+
+        assert_eq!(Err(ProtoErr::NameTooLong), QName::read(&mut reader));
+    }
+
+    #[test]
+    fn test_read_name_max_len() {
+        let mut buf = vec![];
+        for _ in 0..3 {
+            buf.push(63u8);
+            buf.extend_from_slice(&[b'a'; 63]);
+        }
+        buf.push(61u8);
+        buf.extend_from_slice(&[b'a'; 61]);
+        buf.push(0);
+        let mut reader = Reader::new(&buf);
+
+        assert_eq!(255, buf.len());
+        assert!(QName::read(&mut reader).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum QType {
     UNKNOWN(u16),
     A,
@@ -8,6 +8,8 @@ pub enum QType {
     PTR,
     MX,
     TXT,
+    AAAA,
+    SRV,
     OPTION,
 }
 
@@ -21,11 +23,14 @@ impl QType {
             12 => QType::PTR,
             15 => QType::MX,
             16 => QType::TXT,
+            28 => QType::AAAA,
+            33 => QType::SRV,
             41 => QType::OPTION,
             _ => QType::UNKNOWN(num),
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(qtype: &str) -> Option<QType> {
         match qtype {
             "A" => Some(QType::A),
@@ -35,6 +40,8 @@ impl QType {
             "PTR" => Some(QType::PTR),
             "MX" => Some(QType::MX),
             "TXT" => Some(QType::TXT),
+            "AAAA" => Some(QType::AAAA),
+            "SRV" => Some(QType::SRV),
             _ => None,
         }
     }
@@ -48,6 +55,8 @@ impl QType {
             QType::PTR => 12,
             QType::MX => 15,
             QType::TXT => 16,
+            QType::AAAA => 28,
+            QType::SRV => 33,
             QType::OPTION => 41,
             QType::UNKNOWN(num) => *num,
         }
@@ -1,10 +1,11 @@
+use crate::dns::proto::error::ProtoResult;
 use crate::dns::proto::qclass::QClass;
 use crate::dns::proto::qname::QName;
 use crate::dns::proto::qtype::QType;
 use crate::dns::proto::reader::Reader;
 use crate::dns::proto::writer::Writer;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Question {
     pub qname: QName,
     pub qtype: QType,
@@ -24,23 +25,16 @@ impl Question {
     }
 
     pub fn write(&self, writer: &mut Writer) {
-        for label in self.qname.labels() {
-            let bytes = label.as_bytes().to_vec();
-            writer.write_u8(bytes.len() as u8);
-            writer.write_vec(&bytes);
-        }
-
-        writer.write_u8(0);
-
+        self.qname.write(writer);
         writer.write_u16(self.qtype.to_num());
         writer.write_u16(self.class.to_num());
     }
 
-    pub fn read(reader: &mut Reader) -> Question {
-        Question {
-            qname: QName::read(reader),
-            qtype: QType::from_num(reader.read_u16()),
-            class: QClass::from_num(reader.read_u16()),
-        }
+    pub fn read(reader: &mut Reader) -> ProtoResult<Question> {
+        Ok(Question {
+            qname: QName::read(reader)?,
+            qtype: QType::from_num(reader.read_u16()?),
+            class: QClass::from_num(reader.read_u16()?),
+        })
     }
 }
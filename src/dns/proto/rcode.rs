@@ -0,0 +1,93 @@
+/// Response code. Codes above 15 are extended, RFC 6891: their lower 4 bits
+/// go in the header and the upper 8 bits in the OPT record.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rcode {
+    UNKNOWN(u16),
+    NOERROR,
+    FORMERR,
+    SERVFAIL,
+    NXDOMAIN,
+    NOTIMP,
+    REFUSED,
+    YXDOMAIN,
+    YXRRSET,
+    NXRRSET,
+    NOTAUTH,
+    NOTZONE,
+    BADVERS,
+    BADCOOKIE,
+}
+
+impl Rcode {
+    pub fn from_num(num: u16) -> Rcode {
+        match num {
+            0 => Rcode::NOERROR,
+            1 => Rcode::FORMERR,
+            2 => Rcode::SERVFAIL,
+            3 => Rcode::NXDOMAIN,
+            4 => Rcode::NOTIMP,
+            5 => Rcode::REFUSED,
+            6 => Rcode::YXDOMAIN,
+            7 => Rcode::YXRRSET,
+            8 => Rcode::NXRRSET,
+            9 => Rcode::NOTAUTH,
+            10 => Rcode::NOTZONE,
+            16 => Rcode::BADVERS,
+            23 => Rcode::BADCOOKIE,
+            _ => Rcode::UNKNOWN(num),
+        }
+    }
+
+    pub fn to_num(&self) -> u16 {
+        match self {
+            Rcode::NOERROR => 0,
+            Rcode::FORMERR => 1,
+            Rcode::SERVFAIL => 2,
+            Rcode::NXDOMAIN => 3,
+            Rcode::NOTIMP => 4,
+            Rcode::REFUSED => 5,
+            Rcode::YXDOMAIN => 6,
+            Rcode::YXRRSET => 7,
+            Rcode::NXRRSET => 8,
+            Rcode::NOTAUTH => 9,
+            Rcode::NOTZONE => 10,
+            Rcode::BADVERS => 16,
+            Rcode::BADCOOKIE => 23,
+            Rcode::UNKNOWN(x) => *x,
+        }
+    }
+
+    /// Joins the header RCODE and the extended RCODE of an OPT record
+    pub fn from_parts(rcode: u8, ext_rcode: u8) -> Rcode {
+        Rcode::from_num(((ext_rcode as u16) << 4) | (rcode & 0xF) as u16)
+    }
+
+    /// Lower 4 bits, carried in the header
+    pub fn header_part(&self) -> u8 {
+        (self.to_num() & 0xF) as u8
+    }
+
+    /// Upper 8 bits, carried in the OPT record
+    pub fn ext_part(&self) -> u8 {
+        (self.to_num() >> 4) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts() {
+        assert_eq!(
+            (3, 0),
+            (Rcode::NXDOMAIN.header_part(), Rcode::NXDOMAIN.ext_part())
+        );
+        assert_eq!(
+            (7, 1),
+            (Rcode::BADCOOKIE.header_part(), Rcode::BADCOOKIE.ext_part())
+        );
+        assert_eq!(Rcode::BADVERS, Rcode::from_parts(0, 1));
+        assert_eq!(Rcode::UNKNOWN(4095), Rcode::from_parts(0xF, 0xFF));
+    }
+}
@@ -1,10 +1,12 @@
+use crate::dns::proto::error::{ProtoErr, ProtoResult};
+
 pub struct Reader<'a> {
     pos: usize,
-    buf: &'a Vec<u8>,
+    buf: &'a [u8],
 }
 
 impl<'a> Reader<'a> {
-    pub fn new(buf: &'a Vec<u8>) -> Reader {
+    pub fn new(buf: &'a [u8]) -> Reader<'a> {
         Reader { pos: 0, buf }
     }
 
@@ -14,40 +16,45 @@ impl<'a> Reader<'a> {
         old_pos
     }
 
-    pub fn read_u32(&mut self) -> u32 {
-        let b0 = self.buf[self.pos + 0] as u32;
-        let b1 = self.buf[self.pos + 1] as u32;
-        let b2 = self.buf[self.pos + 2] as u32;
-        let b3 = self.buf[self.pos + 3] as u32;
-        self.pos = self.pos + 4;
-        (b0 << 24) | (b1 << 16) | (b2 << 8) | b3
+    /// Consumes next `len` bytes, failing if the buffer is shorter
+    fn take(&mut self, len: usize) -> ProtoResult<&'a [u8]> {
+        if len > self.reminder() {
+            return Err(ProtoErr::Truncated);
+        }
+
+        self.pos += len;
+        Ok(&self.buf[self.pos - len..self.pos])
+    }
+
+    pub fn read_u32(&mut self) -> ProtoResult<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
     }
 
-    pub fn read_u16(&mut self) -> u16 {
-        let b0 = self.buf[self.pos + 0] as u16;
-        let b1 = self.buf[self.pos + 1] as u16;
-        self.pos = self.pos + 2;
-        (b0 << 8) | b1
+    pub fn read_u16(&mut self) -> ProtoResult<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
     }
 
-    pub fn read_u8(&mut self) -> u8 {
-        self.pos = self.pos + 1;
-        self.buf[self.pos - 1]
+    pub fn read_u8(&mut self) -> ProtoResult<u8> {
+        Ok(self.take(1)?[0])
     }
 
-    pub fn read_vec(&mut self, len: usize) -> Vec<u8> {
-        self.pos = self.pos + len;
-        self.buf[self.pos - len..self.pos].to_vec()
+    pub fn read_vec(&mut self, len: usize) -> ProtoResult<Vec<u8>> {
+        Ok(self.take(len)?.to_vec())
     }
 
-    pub fn read_str(&mut self, len: usize) -> String {
-        self.pos = self.pos + len;
+    /// Reads a label, which must be valid UTF-8
+    pub fn read_str(&mut self, len: usize) -> ProtoResult<String> {
+        String::from_utf8(self.read_vec(len)?).map_err(|_| ProtoErr::BadLabel)
+    }
 
-        String::from_utf8(self.buf[self.pos - len..self.pos].to_vec()).unwrap()
+    pub fn pos(&self) -> usize {
+        self.pos
     }
 
     pub fn reminder(&self) -> usize {
-        self.buf.len() - self.pos
+        self.buf.len().saturating_sub(self.pos)
     }
 }
 
@@ -69,8 +76,8 @@ mod tests {
         let buf = vec![0xDEu8, 0xAD, 0xBE, 0xEF];
         let mut rdr = Reader::new(&buf);
 
-        assert_eq!(0xDEAD, rdr.read_u16());
-        assert_eq!(0xBEEF, rdr.read_u16());
+        assert_eq!(Ok(0xDEAD), rdr.read_u16());
+        assert_eq!(Ok(0xBEEF), rdr.read_u16());
     }
 
     #[test]
@@ -78,7 +85,27 @@ mod tests {
         let buf = vec![0xDEu8, 0xAD];
         let mut rdr = Reader::new(&buf);
 
-        assert_eq!(0xDE, rdr.read_u8());
-        assert_eq!(0xAD, rdr.read_u8());
+        assert_eq!(Ok(0xDE), rdr.read_u8());
+        assert_eq!(Ok(0xAD), rdr.read_u8());
+    }
+
+    #[test]
+    fn test_read_truncated() {
+        let buf = vec![0xDEu8, 0xAD, 0xBE];
+        let mut rdr = Reader::new(&buf);
+
+        assert_eq!(Err(ProtoErr::Truncated), rdr.read_u32());
+        assert_eq!(Ok(0xDEAD), rdr.read_u16());
+        assert_eq!(Err(ProtoErr::Truncated), rdr.read_u16());
+        assert_eq!(Err(ProtoErr::Truncated), rdr.read_vec(2));
+        assert_eq!(Ok(0xBE), rdr.read_u8());
+    }
+
+    #[test]
+    fn test_read_str_utf8() {
+        let buf = vec![0xFFu8, 0xFE];
+        let mut rdr = Reader::new(&buf);
+
+        assert_eq!(Err(ProtoErr::BadLabel), rdr.read_str(2));
     }
 }
@@ -1,12 +1,17 @@
+use crate::dns::proto::edns::EdnsOption;
+use crate::dns::proto::error::{ProtoErr, ProtoResult};
 use crate::dns::proto::qclass::QClass;
 use crate::dns::proto::qname::QName;
 use crate::dns::proto::qtype::QType;
 use crate::dns::proto::reader::Reader;
 use crate::dns::proto::writer::Writer;
 
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 
-#[derive(Debug)]
+/// DO flag in the TTL field of an OPT record
+const DNSSEC_OK: u16 = 0x8000;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Record {
     UNKNOWN {
         qname: QName,
@@ -58,116 +63,620 @@ pub enum Record {
         preference: u16,
         exchange: QName,
     },
+    /// Character-strings as sent, which may be any bytes
     TXT {
         qname: QName,
         class: QClass,
         ttl: u32,
-        data: String,
+        data: Vec<Vec<u8>>,
+    },
+    AAAA {
+        qname: QName,
+        class: QClass,
+        ttl: u32,
+        addr: Ipv6Addr,
+    },
+    SRV {
+        qname: QName,
+        class: QClass,
+        ttl: u32,
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: QName,
     },
+    /// EDNS pseudo-record, RFC 6891
     Option {
+        /// Largest UDP payload the sender accepts
         payload_size: u16,
-        rcode: u32,
-        rdata: Vec<u8>,
+        /// Upper 8 bits of the 12 bit RCODE
+        ext_rcode: u8,
+        version: u8,
+        /// DNSSEC OK
+        dnssec_ok: bool,
+        options: Vec<EdnsOption>,
     },
 }
 
 impl Record {
+    /// Owner name, `None` for the OPT pseudo-record which always belongs to the root
+    pub fn qname(&self) -> Option<&QName> {
+        match self {
+            Record::UNKNOWN { qname, .. }
+            | Record::A { qname, .. }
+            | Record::NS { qname, .. }
+            | Record::SOA { qname, .. }
+            | Record::CNAME { qname, .. }
+            | Record::PTR { qname, .. }
+            | Record::MX { qname, .. }
+            | Record::TXT { qname, .. }
+            | Record::AAAA { qname, .. }
+            | Record::SRV { qname, .. } => Some(qname),
+            Record::Option { .. } => None,
+        }
+    }
+
+    pub fn qtype(&self) -> QType {
+        match self {
+            Record::UNKNOWN { qtype, .. } => qtype.clone(),
+            Record::A { .. } => QType::A,
+            Record::NS { .. } => QType::NS,
+            Record::SOA { .. } => QType::SOA,
+            Record::CNAME { .. } => QType::CNAME,
+            Record::PTR { .. } => QType::PTR,
+            Record::MX { .. } => QType::MX,
+            Record::TXT { .. } => QType::TXT,
+            Record::AAAA { .. } => QType::AAAA,
+            Record::SRV { .. } => QType::SRV,
+            Record::Option { .. } => QType::OPTION,
+        }
+    }
+
+    /// Whether both records belong to the same RRset
+    pub fn same_rrset(&self, other: &Record) -> bool {
+        let name = |record: &Record| record.qname().map(|qname| qname.fqdn().to_lowercase());
+        self.qtype() == other.qtype() && name(self) == name(other)
+    }
+
+    /// Time to live, `None` for the OPT pseudo-record which uses the field otherwise
+    pub fn ttl(&self) -> Option<u32> {
+        match self {
+            Record::UNKNOWN { ttl, .. }
+            | Record::A { ttl, .. }
+            | Record::NS { ttl, .. }
+            | Record::SOA { ttl, .. }
+            | Record::CNAME { ttl, .. }
+            | Record::PTR { ttl, .. }
+            | Record::MX { ttl, .. }
+            | Record::TXT { ttl, .. }
+            | Record::AAAA { ttl, .. }
+            | Record::SRV { ttl, .. } => Some(*ttl),
+            Record::Option { .. } => None,
+        }
+    }
+
+    pub(crate) fn set_ttl(&mut self, val: u32) {
+        match self {
+            Record::UNKNOWN { ttl, .. }
+            | Record::A { ttl, .. }
+            | Record::NS { ttl, .. }
+            | Record::SOA { ttl, .. }
+            | Record::CNAME { ttl, .. }
+            | Record::PTR { ttl, .. }
+            | Record::MX { ttl, .. }
+            | Record::TXT { ttl, .. }
+            | Record::AAAA { ttl, .. }
+            | Record::SRV { ttl, .. } => *ttl = val,
+            Record::Option { .. } => {}
+        }
+    }
+
+    /// Character-strings for a text, split every 255 bytes
+    pub fn txt_strings(text: &str) -> Vec<Vec<u8>> {
+        if text.is_empty() {
+            return vec![vec![]];
+        }
+
+        text.as_bytes()
+            .chunks(255)
+            .map(|chunk| chunk.to_vec())
+            .collect()
+    }
+
     pub fn write(&self, writer: &mut Writer) {
         match self {
+            Record::UNKNOWN {
+                qname,
+                qtype,
+                class,
+                ttl,
+                rdata,
+            } => {
+                Record::write_preamble(writer, qname, qtype, class, *ttl);
+                Record::write_rdata(writer, |writer| writer.write_vec(rdata));
+            }
+            Record::A {
+                qname,
+                class,
+                ttl,
+                addr,
+            } => {
+                Record::write_preamble(writer, qname, &QType::A, class, *ttl);
+                Record::write_rdata(writer, |writer| writer.write_vec(&addr.octets()));
+            }
+            Record::NS {
+                qname,
+                class,
+                ttl,
+                nsdname,
+            } => {
+                Record::write_preamble(writer, qname, &QType::NS, class, *ttl);
+                Record::write_rdata(writer, |writer| nsdname.write(writer));
+            }
+            Record::SOA {
+                qname,
+                class,
+                ttl,
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                Record::write_preamble(writer, qname, &QType::SOA, class, *ttl);
+                Record::write_rdata(writer, |writer| {
+                    mname.write(writer);
+                    rname.write(writer);
+                    writer.write_u32(*serial);
+                    writer.write_u32(*refresh);
+                    writer.write_u32(*retry);
+                    writer.write_u32(*expire);
+                    writer.write_u32(*minimum);
+                });
+            }
+            Record::CNAME {
+                qname,
+                class,
+                ttl,
+                cname,
+            } => {
+                Record::write_preamble(writer, qname, &QType::CNAME, class, *ttl);
+                Record::write_rdata(writer, |writer| cname.write(writer));
+            }
+            Record::PTR {
+                qname,
+                class,
+                ttl,
+                ptrdname,
+            } => {
+                Record::write_preamble(writer, qname, &QType::PTR, class, *ttl);
+                Record::write_rdata(writer, |writer| ptrdname.write(writer));
+            }
+            Record::MX {
+                qname,
+                class,
+                ttl,
+                preference,
+                exchange,
+            } => {
+                Record::write_preamble(writer, qname, &QType::MX, class, *ttl);
+                Record::write_rdata(writer, |writer| {
+                    writer.write_u16(*preference);
+                    exchange.write(writer);
+                });
+            }
+            Record::TXT {
+                qname,
+                class,
+                ttl,
+                data,
+            } => {
+                Record::write_preamble(writer, qname, &QType::TXT, class, *ttl);
+                Record::write_rdata(writer, |writer| {
+                    // TXT-DATA is one or more <character-string>s of up to 255 bytes each,
+                    // longer ones are split rather than written corrupt
+                    if data.is_empty() {
+                        writer.write_u8(0);
+                    }
+                    for string in data.iter() {
+                        if string.is_empty() {
+                            writer.write_u8(0);
+                        }
+                        for chunk in string.chunks(255) {
+                            writer.write_u8(chunk.len() as u8);
+                            writer.write_vec(chunk);
+                        }
+                    }
+                });
+            }
+            Record::AAAA {
+                qname,
+                class,
+                ttl,
+                addr,
+            } => {
+                Record::write_preamble(writer, qname, &QType::AAAA, class, *ttl);
+                Record::write_rdata(writer, |writer| writer.write_vec(&addr.octets()));
+            }
+            Record::SRV {
+                qname,
+                class,
+                ttl,
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                Record::write_preamble(writer, qname, &QType::SRV, class, *ttl);
+                Record::write_rdata(writer, |writer| {
+                    writer.write_u16(*priority);
+                    writer.write_u16(*weight);
+                    writer.write_u16(*port);
+                    // RFC 2782 forbids compressing the target
+                    target.write_uncompressed(writer);
+                });
+            }
             Record::Option {
                 payload_size,
-                rcode,
-                rdata,
+                ext_rcode,
+                version,
+                dnssec_ok,
+                options,
             } => {
                 writer.write_u8(0);
                 writer.write_u16(QType::OPTION.to_num());
                 writer.write_u16(*payload_size);
-                writer.write_u32(*rcode);
-                writer.write_u16(rdata.len() as u16);
-                writer.write_vec(rdata);
+                writer.write_u8(*ext_rcode);
+                writer.write_u8(*version);
+                writer.write_u16(if *dnssec_ok { DNSSEC_OK } else { 0 });
+                Record::write_rdata(writer, |writer| {
+                    for option in options.iter() {
+                        option.write(writer);
+                    }
+                });
             }
-            _ => unimplemented!(),
         }
     }
 
-    pub fn read(reader: &mut Reader) -> Record {
-        let qname = QName::read(reader);
-        let qtype = QType::from_num(reader.read_u16());
-        let class = QClass::from_num(reader.read_u16());
-        let ttl = reader.read_u32();
-        let rdata_len = reader.read_u16() as usize;
+    fn write_preamble(writer: &mut Writer, qname: &QName, qtype: &QType, class: &QClass, ttl: u32) {
+        qname.write(writer);
+        writer.write_u16(qtype.to_num());
+        writer.write_u16(class.to_num());
+        writer.write_u32(ttl);
+    }
 
-        match qtype {
-            QType::A => Record::A {
-                qname,
-                class,
-                ttl,
-                addr: Ipv4Addr::new(
-                    reader.read_u8(),
-                    reader.read_u8(),
-                    reader.read_u8(),
-                    reader.read_u8(),
-                ),
-            },
+    /// Writes RDLENGTH followed by RDATA,
+    /// length is backfilled once the RDATA has been written
+    fn write_rdata<F>(writer: &mut Writer, rdata: F)
+    where
+        F: FnOnce(&mut Writer),
+    {
+        let pos = writer.pos();
+        writer.write_u16(0);
+
+        rdata(writer);
+
+        let rdata_len = writer.pos() - pos - 2;
+        writer.set_u16(pos, rdata_len as u16);
+    }
+
+    pub fn read(reader: &mut Reader) -> ProtoResult<Record> {
+        let qname = QName::read(reader)?;
+        let qtype = QType::from_num(reader.read_u16()?);
+        let class = QClass::from_num(reader.read_u16()?);
+        let ttl = reader.read_u32()?;
+        let rdata_len = reader.read_u16()? as usize;
+
+        if rdata_len > reader.reminder() {
+            return Err(ProtoErr::Truncated);
+        }
+
+        let rdata_end = reader.pos() + rdata_len;
+
+        let record = match qtype {
+            QType::A => {
+                if rdata_len != 4 {
+                    return Err(ProtoErr::BadRdataLength);
+                }
+
+                Record::A {
+                    qname,
+                    class,
+                    ttl,
+                    addr: Ipv4Addr::new(
+                        reader.read_u8()?,
+                        reader.read_u8()?,
+                        reader.read_u8()?,
+                        reader.read_u8()?,
+                    ),
+                }
+            }
             QType::NS => Record::NS {
                 qname,
                 class,
                 ttl,
-                nsdname: QName::read(reader),
+                nsdname: QName::read(reader)?,
             },
             QType::SOA => Record::SOA {
                 qname,
                 class,
                 ttl,
-                mname: QName::read(reader),
-                rname: QName::read(reader),
-                serial: reader.read_u32(),
-                refresh: reader.read_u32(),
-                retry: reader.read_u32(),
-                expire: reader.read_u32(),
-                minimum: reader.read_u32(),
+                mname: QName::read(reader)?,
+                rname: QName::read(reader)?,
+                serial: reader.read_u32()?,
+                refresh: reader.read_u32()?,
+                retry: reader.read_u32()?,
+                expire: reader.read_u32()?,
+                minimum: reader.read_u32()?,
             },
             QType::CNAME => Record::CNAME {
                 qname,
                 class,
                 ttl,
-                cname: QName::read(reader),
+                cname: QName::read(reader)?,
             },
             QType::PTR => Record::PTR {
                 qname,
                 class,
                 ttl,
-                ptrdname: QName::read(reader),
+                ptrdname: QName::read(reader)?,
             },
             QType::MX => Record::MX {
                 qname,
                 class,
                 ttl,
-                preference: reader.read_u16(),
-                exchange: QName::read(reader),
+                preference: reader.read_u16()?,
+                exchange: QName::read(reader)?,
             },
             QType::TXT => {
-                let data_len = reader.read_u8();
+                let mut data = vec![];
+
+                while reader.pos() < rdata_end {
+                    let data_len = reader.read_u8()? as usize;
+                    if reader.pos() + data_len > rdata_end {
+                        return Err(ProtoErr::BadRdataLength);
+                    }
+                    data.push(reader.read_vec(data_len)?);
+                }
 
                 Record::TXT {
                     qname,
                     class,
                     ttl,
-                    data: reader.read_str(data_len as usize),
+                    data,
+                }
+            }
+            QType::AAAA => {
+                if rdata_len != 16 {
+                    return Err(ProtoErr::BadRdataLength);
+                }
+
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&reader.read_vec(16)?);
+
+                Record::AAAA {
+                    qname,
+                    class,
+                    ttl,
+                    addr: Ipv6Addr::from(octets),
                 }
             }
-            QType::OPTION => Record::Option {
-                payload_size: class.to_num(),
-                rcode: ttl,
-                rdata: reader.read_vec(rdata_len),
+            QType::SRV => Record::SRV {
+                qname,
+                class,
+                ttl,
+                priority: reader.read_u16()?,
+                weight: reader.read_u16()?,
+                port: reader.read_u16()?,
+                target: QName::read(reader)?,
             },
+            QType::OPTION => {
+                let mut options = vec![];
+                while reader.pos() < rdata_end {
+                    options.push(EdnsOption::read(reader)?);
+                }
+
+                Record::Option {
+                    payload_size: class.to_num(),
+                    ext_rcode: (ttl >> 24) as u8,
+                    version: (ttl >> 16) as u8,
+                    dnssec_ok: ttl as u16 & DNSSEC_OK != 0,
+                    options,
+                }
+            }
             _ => Record::UNKNOWN {
                 qname,
                 qtype,
                 class,
                 ttl,
-                rdata: reader.read_vec(rdata_len),
+                rdata: reader.read_vec(rdata_len)?,
             },
+        };
+
+        if reader.pos() != rdata_end {
+            return Err(ProtoErr::BadRdataLength);
+        }
+
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(record: Record) {
+        let mut buf: Vec<u8> = vec![];
+        record.write(&mut Writer::new(&mut buf));
+
+        let mut reader = Reader::new(&buf);
+        assert_eq!(Ok(record), Record::read(&mut reader));
+        assert_eq!(0, reader.reminder());
+    }
+
+    #[test]
+    fn test_write_a() {
+        let mut buf: Vec<u8> = vec![];
+        let record = Record::A {
+            qname: QName::from_str("web.docker."),
+            class: QClass::INTERNET,
+            ttl: 60,
+            addr: Ipv4Addr::new(172, 17, 0, 2),
+        };
+        record.write(&mut Writer::new(&mut buf));
+
+        assert_eq!(
+            vec![
+                3, b'w', b'e', b'b', 6, b'd', b'o', b'c', b'k', b'e', b'r', 0, // qname
+                0, 1, // type
+                0, 1, // class
+                0, 0, 0, 60, // ttl
+                0, 4, // rdlength
+                172, 17, 0, 2, // rdata
+            ],
+            buf
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let qname = || QName::from_str("web.docker");
+
+        roundtrip(Record::A {
+            qname: qname(),
+            class: QClass::INTERNET,
+            ttl: 60,
+            addr: Ipv4Addr::new(172, 17, 0, 2),
+        });
+        roundtrip(Record::NS {
+            qname: qname(),
+            class: QClass::INTERNET,
+            ttl: 60,
+            nsdname: QName::from_str("ns.docker"),
+        });
+        roundtrip(Record::SOA {
+            qname: qname(),
+            class: QClass::INTERNET,
+            ttl: 60,
+            mname: QName::from_str("ns.docker"),
+            rname: QName::from_str("hostmaster.docker"),
+            serial: 1,
+            refresh: 2,
+            retry: 3,
+            expire: 4,
+            minimum: 5,
+        });
+        roundtrip(Record::CNAME {
+            qname: qname(),
+            class: QClass::INTERNET,
+            ttl: 60,
+            cname: QName::from_str("app.docker"),
+        });
+        roundtrip(Record::PTR {
+            qname: QName::from_str("2.0.17.172.in-addr.arpa"),
+            class: QClass::INTERNET,
+            ttl: 60,
+            ptrdname: qname(),
+        });
+        roundtrip(Record::MX {
+            qname: qname(),
+            class: QClass::INTERNET,
+            ttl: 60,
+            preference: 10,
+            exchange: QName::from_str("mail.docker"),
+        });
+        roundtrip(Record::TXT {
+            qname: qname(),
+            class: QClass::INTERNET,
+            ttl: 60,
+            data: Record::txt_strings(&"x".repeat(300)),
+        });
+        // separate strings stay separate, bytes are kept as they are
+        roundtrip(Record::TXT {
+            qname: qname(),
+            class: QClass::INTERNET,
+            ttl: 60,
+            data: vec![b"a".to_vec(), vec![], vec![0xFF, 0xFE, b'b']],
+        });
+        roundtrip(Record::AAAA {
+            qname: qname(),
+            class: QClass::INTERNET,
+            ttl: 60,
+            addr: "2001:db8:1::242:ac11:2".parse().unwrap(),
+        });
+        roundtrip(Record::SRV {
+            qname: QName::from_str("_http._tcp.web.docker"),
+            class: QClass::INTERNET,
+            ttl: 60,
+            priority: 0,
+            weight: 0,
+            port: 8080,
+            target: qname(),
+        });
+        roundtrip(Record::UNKNOWN {
+            qname: qname(),
+            qtype: QType::UNKNOWN(99),
+            class: QClass::INTERNET,
+            ttl: 60,
+            rdata: vec![0xDE, 0xAD, 0xBE, 0xEF],
+        });
+        roundtrip(Record::Option {
+            payload_size: 4096,
+            ext_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: vec![],
+        });
+        roundtrip(Record::Option {
+            payload_size: 1232,
+            ext_rcode: 1,
+            version: 0,
+            dnssec_ok: true,
+            options: vec![
+                EdnsOption::Cookie {
+                    client: [1, 2, 3, 4, 5, 6, 7, 8],
+                    server: vec![],
+                },
+                EdnsOption::Padding(4),
+            ],
+        });
+    }
+
+    #[test]
+    fn test_read_bad_rdata_length() {
+        let mut buf: Vec<u8> = vec![];
+        Record::CNAME {
+            qname: QName::from_str("web.docker"),
+            class: QClass::INTERNET,
+            ttl: 60,
+            cname: QName::from_str("app.docker"),
         }
+        .write(&mut Writer::new(&mut buf));
+        buf.push(0);
+
+        // claim one byte more of RDATA than the name takes
+        let pos = buf.len() - 8;
+        buf[pos] += 1;
+
+        let mut reader = Reader::new(&buf);
+        assert_eq!(Err(ProtoErr::BadRdataLength), Record::read(&mut reader));
+    }
+
+    #[test]
+    fn test_read_truncated() {
+        let mut buf: Vec<u8> = vec![];
+        Record::A {
+            qname: QName::from_str("web.docker"),
+            class: QClass::INTERNET,
+            ttl: 60,
+            addr: Ipv4Addr::new(172, 17, 0, 2),
+        }
+        .write(&mut Writer::new(&mut buf));
+        buf.pop();
+
+        let mut reader = Reader::new(&buf);
+        assert_eq!(Err(ProtoErr::Truncated), Record::read(&mut reader));
     }
 }
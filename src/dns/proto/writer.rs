@@ -1,10 +1,27 @@
+use std::collections::HashMap;
+
+/// Largest offset that fits in a compression pointer
+const MAX_POINTER: usize = 0x3FFF;
+
 pub struct Writer<'a> {
     buf: &'a mut Vec<u8>,
+    /// Position of message start in the buffer
+    start: usize,
+    /// Offsets of names already written, for compression
+    names: HashMap<String, u16>,
 }
 
 impl<'a> Writer<'a> {
-    pub fn new(buf: &mut Vec<u8>) -> Writer {
-        Writer { buf }
+    pub fn new(buf: &mut Vec<u8>) -> Writer<'_> {
+        Writer {
+            start: buf.len(),
+            buf,
+            names: HashMap::new(),
+        }
+    }
+
+    pub fn pos(&self) -> usize {
+        self.buf.len()
     }
 
     pub fn write_u8(&mut self, byte: u8) {
@@ -12,29 +29,37 @@ impl<'a> Writer<'a> {
     }
 
     pub fn write_u16(&mut self, val: u16) {
-        let b0 = ((val & 0xff << 0) >> 0) as u8;
-        let b1 = ((val & 0xff << 8) >> 8) as u8;
-
-        self.buf.push(b1);
-        self.buf.push(b0);
+        self.write_vec(&val.to_be_bytes());
     }
 
     pub fn write_u32(&mut self, val: u32) {
-        let b0 = ((val & 0xff << 0) >> 0) as u8;
-        let b1 = ((val & 0xff << 8) >> 8) as u8;
-        let b2 = ((val & 0xff << 16) >> 16) as u8;
-        let b3 = ((val & 0xff << 24) >> 24) as u8;
-        self.write_vec(&vec![b3, b2, b1, b0]);
+        self.write_vec(&val.to_be_bytes());
     }
 
-    pub fn write_vec(&mut self, vec: &Vec<u8>) {
-        let mut idx = 0;
+    pub fn write_vec(&mut self, vec: &[u8]) {
+        self.buf.extend_from_slice(vec);
+    }
+
+    /// Offset of a previously written name, to point at instead of repeating it
+    pub fn find_name(&self, name: &str) -> Option<u16> {
+        self.names.get(&name.to_lowercase()).copied()
+    }
 
-        while idx < vec.len() {
-            self.buf.push(vec[idx]);
-            idx = idx + 1;
+    /// Remembers that a name starts at the current position
+    pub fn add_name(&mut self, name: &str) {
+        let offset = self.pos() - self.start;
+        if offset <= MAX_POINTER {
+            self.names
+                .entry(name.to_lowercase())
+                .or_insert(offset as u16);
         }
     }
+
+    /// Overwrites previously written u16 at given position,
+    /// used to backfill length fields once the payload is known
+    pub fn set_u16(&mut self, pos: usize, val: u16) {
+        self.buf[pos..pos + 2].copy_from_slice(&val.to_be_bytes());
+    }
 }
 
 #[cfg(test)]
@@ -44,9 +69,33 @@ mod tests {
     #[test]
     fn test_write_u8() {
         let mut buf: Vec<u8> = vec![];
-        let mut writer = Writer { buf: &mut buf };
+        let mut writer = Writer::new(&mut buf);
 
         writer.write_u8(42);
         assert_eq!(42, buf[0])
     }
+
+    #[test]
+    fn test_find_name() {
+        let mut buf: Vec<u8> = vec![0xFF];
+        let mut writer = Writer::new(&mut buf);
+
+        writer.write_u16(0);
+        writer.add_name("Web.docker");
+
+        assert_eq!(Some(2), writer.find_name("web.DOCKER"));
+        assert_eq!(None, writer.find_name("docker"));
+    }
+
+    #[test]
+    fn test_set_u16() {
+        let mut buf: Vec<u8> = vec![];
+        let mut writer = Writer::new(&mut buf);
+
+        writer.write_u16(0);
+        writer.write_u32(0xDEADBEEF);
+        writer.set_u16(0, 0xCAFE);
+
+        assert_eq!(vec![0xCA, 0xFE, 0xDE, 0xAD, 0xBE, 0xEF], buf)
+    }
 }
@@ -1,13 +1,1112 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
 use crate::dns::proto::message::Message;
+use crate::dns::proto::qclass::QClass;
+use crate::dns::proto::qname::QName;
+use crate::dns::proto::qtype::QType;
+use crate::dns::proto::question::Question;
+use crate::dns::proto::rcode::Rcode;
+use crate::dns::proto::record::Record;
+use crate::docker::model::{Container, Port};
+
+const DEFAULT_TTL: u32 = 10;
+
+const DEFAULT_HOST: &str = "localhost.";
+
+const COMPOSE_PROJECT: &str = "com.docker.compose.project";
+const COMPOSE_SERVICE: &str = "com.docker.compose.service";
+const COMPOSE_NUMBER: &str = "com.docker.compose.container-number";
+
+/// Extra names, comma separated, relative to the zone unless they end with a dot
+const LABEL_NAME: &str = "dhns.name";
+/// Extra names answered with a CNAME to the container
+const LABEL_CNAME: &str = "dhns.cname";
+const LABEL_TXT: &str = "dhns.txt";
+const LABEL_TTL: &str = "dhns.ttl";
+
+/// Container as seen by the resolver
+#[derive(Debug)]
+struct Entry {
+    /// Container names first, then names shared with other replicas
+    names: Vec<String>,
+    ipv4: Vec<Ipv4Addr>,
+    ipv6: Vec<Ipv6Addr>,
+    ports: Vec<Port>,
+    /// Names that only resolve to the address on one network
+    scopes: Vec<Scope>,
+    /// Names that are an alias for the container name
+    cnames: Vec<String>,
+    txt: Vec<String>,
+    ttl: Option<u32>,
+}
+
+/// Container on a single network, answering to `<name>.<network>` for its names and aliases
+#[derive(Debug)]
+struct Scope {
+    network: String,
+    names: Vec<String>,
+    ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+}
+
+pub struct Resolver {
+    /// Zone we are authoritative for, e.g. `docker`
+    zone: Vec<String>,
+    /// Target of SRV records for ports published on the Docker host
+    host: QName,
+    ttl: u32,
+    /// Running containers, keyed by container ID
+    entries: HashMap<String, Entry>,
+    /// Domains outside the zone containers may claim names in, none by default
+    foreign_suffixes: Vec<Vec<String>>,
+}
+
+impl Entry {
+    fn new(container: &Container, zone: &[String]) -> Entry {
+        let mut ipv4: Vec<Ipv4Addr> = container.networks.values().filter_map(|e| e.ipv4).collect();
+        let mut ipv6: Vec<Ipv6Addr> = container.networks.values().filter_map(|e| e.ipv6).collect();
 
-pub struct Resolver {}
+        // networks come in no particular order, keep answers stable
+        ipv4.sort();
+        ipv6.sort();
+
+        let mut names: Vec<String> = container
+            .names
+            .iter()
+            .map(|name| name.to_lowercase())
+            .collect();
+
+        // Compose replicas answer to `<n>.<service>.<project>` and, all together, `<service>.<project>`
+        let labels = &container.labels;
+        if let (Some(project), Some(service)) =
+            (labels.get(COMPOSE_PROJECT), labels.get(COMPOSE_SERVICE))
+        {
+            let service = format!("{}.{}", service, project).to_lowercase();
+            if let Some(number) = labels.get(COMPOSE_NUMBER) {
+                names.push(format!("{}.{}", number, service));
+            }
+            names.push(service);
+        }
+
+        if let Some(value) = labels.get(LABEL_NAME) {
+            names.extend(label_names(value, zone));
+        }
+        // names too long for the wire can't be answered, nor be a target
+        names.retain(|name| absolute(name, zone).is_valid());
+
+        let mut scopes: Vec<Scope> = container
+            .networks
+            .iter()
+            .map(|(network, endpoint)| {
+                let network = network.to_lowercase();
+                let mut scoped: Vec<String> = container
+                    .names
+                    .iter()
+                    .chain(endpoint.aliases.iter())
+                    .map(|name| format!("{}.{}", name, network).to_lowercase())
+                    .filter(|name| absolute(name, zone).is_valid())
+                    .collect();
+                scoped.sort();
+                scoped.dedup();
+
+                Scope {
+                    network,
+                    names: scoped,
+                    ipv4: endpoint.ipv4,
+                    ipv6: endpoint.ipv6,
+                }
+            })
+            .collect();
+        scopes.sort_by(|a, b| a.network.cmp(&b.network));
+
+        Entry {
+            names,
+            ipv4,
+            ipv6,
+            ports: container.ports.clone(),
+            scopes,
+            cnames: labels
+                .get(LABEL_CNAME)
+                .map(|value| label_names(value, zone))
+                .unwrap_or_default(),
+            txt: labels.get(LABEL_TXT).cloned().into_iter().collect(),
+            ttl: labels
+                .get(LABEL_TTL)
+                .and_then(|ttl| ttl.trim().parse().ok()),
+        }
+    }
+
+    fn answers_to(&self, name: &str) -> bool {
+        self.names.iter().any(|n| n == name) || self.scope(name).is_some()
+    }
+
+    /// Network a name is scoped to, `None` for names that resolve on every network
+    fn scope(&self, name: &str) -> Option<&Scope> {
+        if self.names.iter().any(|n| n == name) {
+            return None;
+        }
+
+        self.scopes
+            .iter()
+            .find(|scope| scope.names.iter().any(|n| n == name))
+    }
+
+    fn ipv4(&self, name: &str) -> Vec<Ipv4Addr> {
+        match self.scope(name) {
+            Some(scope) => scope.ipv4.into_iter().collect(),
+            None => self.ipv4.clone(),
+        }
+    }
+
+    fn ipv6(&self, name: &str) -> Vec<Ipv6Addr> {
+        match self.scope(name) {
+            Some(scope) => scope.ipv6.into_iter().collect(),
+            None => self.ipv6.clone(),
+        }
+    }
+
+    /// Name of this very container, on the same network as the name it was found by
+    fn target(&self, name: &str) -> Option<String> {
+        let primary = self.names.first()?;
+        match self.scope(name) {
+            Some(scope) => Some(format!("{}.{}", primary, scope.network)),
+            None => Some(primary.clone()),
+        }
+    }
+}
 
 impl Resolver {
-    pub fn new() -> Resolver {
-        Resolver {}
+    pub fn new(zone: &str) -> Resolver {
+        Resolver {
+            zone: QName::from_str(zone)
+                .labels()
+                .iter()
+                .filter(|label| !label.is_empty())
+                .map(|label| label.to_lowercase())
+                .collect(),
+            host: QName::from_str(DEFAULT_HOST),
+            ttl: DEFAULT_TTL,
+            entries: HashMap::new(),
+            foreign_suffixes: vec![],
+        }
+    }
+
+    /// Sets the name of the Docker host, which SRV records for published ports point at
+    pub fn set_host(&mut self, host: &str) {
+        self.host = QName::from_str(host);
+    }
+
+    /// Allows containers to claim absolute names outside the zone with `dhns.name`,
+    /// as long as they fall under one of these domains
+    pub fn set_foreign_suffixes(&mut self, suffixes: &[&str]) {
+        self.foreign_suffixes = suffixes
+            .iter()
+            .map(|suffix| {
+                suffix
+                    .split('.')
+                    .filter(|label| !label.is_empty())
+                    .map(|label| label.to_lowercase())
+                    .collect::<Vec<String>>()
+            })
+            .filter(|labels| !labels.is_empty())
+            .collect();
+    }
+
+    /// Replaces known containers, e.g. with the output of `GET /containers/json`
+    pub fn load(&mut self, containers: &[Container]) {
+        self.entries = containers
+            .iter()
+            .filter(|container| container.running())
+            .map(|container| (container.id.clone(), Entry::new(container, &self.zone)))
+            .collect();
+    }
+
+    /// Adds or replaces a container, dropping it if it is no longer running
+    pub fn update(&mut self, container: &Container) {
+        if container.running() {
+            self.entries
+                .insert(container.id.clone(), Entry::new(container, &self.zone));
+        } else {
+            self.entries.remove(&container.id);
+        }
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.entries.remove(id);
+    }
+
+    /// Name relative to the zone, if it belongs to the zone
+    fn relative(&self, qname: &QName) -> Option<String> {
+        let labels: Vec<String> = qname
+            .labels()
+            .iter()
+            .filter(|label| !label.is_empty())
+            .map(|label| label.to_lowercase())
+            .collect();
+
+        if labels.len() < self.zone.len() || !labels.ends_with(&self.zone) {
+            return None;
+        }
+
+        Some(labels[..labels.len() - self.zone.len()].join("."))
+    }
+
+    /// Fully qualified name outside the zone, if a container declared it with a label
+    /// and it lies under an allowed suffix. Any container could hijack public names otherwise.
+    fn foreign(&self, qname: &QName) -> Option<String> {
+        let labels: Vec<String> = qname
+            .labels()
+            .iter()
+            .filter(|label| !label.is_empty())
+            .map(|label| label.to_lowercase())
+            .collect();
+        if !self
+            .foreign_suffixes
+            .iter()
+            .any(|suffix| labels.ends_with(suffix))
+        {
+            return None;
+        }
+
+        let fqdn = format!("{}.", labels.join("."));
+
+        let name = match Resolver::split_service(&fqdn) {
+            Some((_, _, name)) => name,
+            None => &fqdn,
+        };
+        let declared = self
+            .entries
+            .values()
+            .any(|entry| entry.answers_to(name) || entry.cnames.iter().any(|n| n == name));
+
+        if declared {
+            Some(fqdn)
+        } else {
+            None
+        }
+    }
+
+    /// Splits `_service._proto.name` into port number, protocol and name
+    fn split_service(name: &str) -> Option<(u16, &str, &str)> {
+        let mut parts = name.splitn(3, '.');
+        let service = parts.next()?.strip_prefix('_')?;
+        let proto = parts.next()?.strip_prefix('_')?;
+        let name = parts.next()?;
+
+        Some((service_port(service)?, proto, name))
+    }
+
+    fn absolute(&self, name: &str) -> QName {
+        absolute(name, &self.zone)
+    }
+
+    fn ttl(&self, entry: &Entry) -> u32 {
+        entry.ttl.unwrap_or(self.ttl)
+    }
+
+    /// Containers answering to a name, several for a Compose service with replicas
+    fn lookup(&self, name: &str) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self
+            .entries
+            .values()
+            .filter(|entry| entry.answers_to(name))
+            .collect();
+
+        // entries come in no particular order, keep answers stable
+        entries.sort_by(|a, b| a.names.cmp(&b.names));
+        entries
+    }
+
+    /// Whether some name lies below this one, which then exists without records
+    /// of its own, like the project in `<service>.<project>` (RFC 8020)
+    fn has_descendants(&self, name: &str) -> bool {
+        let suffix = format!(".{}", name);
+
+        self.entries.values().any(|entry| {
+            entry
+                .names
+                .iter()
+                .chain(entry.cnames.iter())
+                .chain(entry.scopes.iter().flat_map(|scope| scope.names.iter()))
+                .any(|n| n.ends_with(&suffix))
+        })
+    }
+
+    fn lookup_cname(&self, name: &str) -> Option<&Entry> {
+        self.entries
+            .values()
+            .find(|entry| entry.cnames.iter().any(|n| n == name))
+    }
+
+    fn lookup_addr(&self, addr: &IpAddr) -> Option<&Entry> {
+        self.entries.values().find(|entry| match addr {
+            IpAddr::V4(addr) => entry.ipv4.contains(addr),
+            IpAddr::V6(addr) => entry.ipv6.contains(addr),
+        })
+    }
+
+    /// Whether we answer a question ourselves rather than leave it to upstream nameservers
+    fn owns(&self, question: &Question) -> bool {
+        match reverse_addr(&question.qname) {
+            Some(addr) => self.lookup_addr(&addr).is_some(),
+            None => {
+                self.relative(&question.qname).is_some() || self.foreign(&question.qname).is_some()
+            }
+        }
+    }
+
+    pub fn is_authoritative(&self, msg: &Message) -> bool {
+        msg.questions().iter().any(|question| self.owns(question))
     }
 
     pub fn resolve(&self, msg: Message) -> Message {
+        let mut reply = Message::response_for(&msg);
+
+        for question in msg.questions() {
+            if let Some(addr) = reverse_addr(&question.qname) {
+                match self.lookup_addr(&addr) {
+                    Some(entry) => {
+                        reply.header_mut().set_aa(true);
+                        if let (QType::PTR, Some(name)) = (&question.qtype, entry.names.first()) {
+                            reply.add_answer(Record::PTR {
+                                qname: question.qname.clone(),
+                                class: QClass::INTERNET,
+                                ttl: self.ttl(entry),
+                                ptrdname: self.absolute(name),
+                            });
+                        }
+                    }
+                    // not one of our containers
+                    None => {
+                        reply.set_rcode(Rcode::REFUSED);
+                    }
+                }
+                continue;
+            }
+
+            let name = match self.relative(&question.qname) {
+                Some(name) => name,
+                None => match self.foreign(&question.qname) {
+                    Some(name) => name,
+                    None => {
+                        reply.set_rcode(Rcode::REFUSED);
+                        continue;
+                    }
+                },
+            };
+
+            reply.header_mut().set_aa(true);
+
+            if name.is_empty() {
+                // zone apex, nothing to serve there yet
+                continue;
+            }
+
+            if let Some((port, proto, name)) = Resolver::split_service(&name) {
+                let entries = self.lookup(name);
+                if entries.is_empty() {
+                    reply.set_rcode(Rcode::NXDOMAIN);
+                } else if let QType::SRV = question.qtype {
+                    for entry in entries {
+                        self.answer_srv(question, name, entry, port, proto, &mut reply);
+                    }
+                }
+                continue;
+            }
+
+            if let Some(entry) = self.lookup_cname(&name) {
+                if let Some(target) = entry.names.first() {
+                    let cname = self.absolute(target);
+                    reply.add_answer(Record::CNAME {
+                        qname: question.qname.clone(),
+                        class: QClass::INTERNET,
+                        ttl: self.ttl(entry),
+                        cname: cname.clone(),
+                    });
+                    // the target is ours as well, save the client another query
+                    self.answer_name(&cname, target, &[entry], &question.qtype, &mut reply);
+                }
+                continue;
+            }
+
+            let entries = self.lookup(&name);
+            if entries.is_empty() {
+                if !self.has_descendants(&name) {
+                    reply.set_rcode(Rcode::NXDOMAIN);
+                }
+                continue;
+            }
+
+            self.answer_name(
+                &question.qname,
+                &name,
+                &entries,
+                &question.qtype,
+                &mut reply,
+            );
+        }
+
+        reply
+    }
+
+    /// Records of the containers answering to a name
+    fn answer_name(
+        &self,
+        qname: &QName,
+        name: &str,
+        entries: &[&Entry],
+        qtype: &QType,
+        reply: &mut Message,
+    ) {
+        // records of one set share a TTL
+        let ttl = entries
+            .iter()
+            .map(|entry| self.ttl(entry))
+            .min()
+            .unwrap_or(self.ttl);
+
+        match qtype {
+            QType::A => {
+                let mut addrs: Vec<Ipv4Addr> =
+                    entries.iter().flat_map(|entry| entry.ipv4(name)).collect();
+                addrs.sort();
+                addrs.dedup();
+
+                for addr in addrs {
+                    reply.add_answer(Record::A {
+                        qname: qname.clone(),
+                        class: QClass::INTERNET,
+                        ttl,
+                        addr,
+                    });
+                }
+            }
+            QType::AAAA => {
+                let mut addrs: Vec<Ipv6Addr> =
+                    entries.iter().flat_map(|entry| entry.ipv6(name)).collect();
+                addrs.sort();
+                addrs.dedup();
+
+                for addr in addrs {
+                    reply.add_answer(Record::AAAA {
+                        qname: qname.clone(),
+                        class: QClass::INTERNET,
+                        ttl,
+                        addr,
+                    });
+                }
+            }
+            QType::TXT => {
+                for text in entries.iter().flat_map(|entry| entry.txt.iter()) {
+                    reply.add_answer(Record::TXT {
+                        qname: qname.clone(),
+                        class: QClass::INTERNET,
+                        ttl,
+                        data: Record::txt_strings(text),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Published ports point at the Docker host and its public port,
+    /// others at the container itself
+    fn answer_srv(
+        &self,
+        question: &Question,
+        name: &str,
+        entry: &Entry,
+        port: u16,
+        proto: &str,
+        reply: &mut Message,
+    ) {
+        let ports = entry
+            .ports
+            .iter()
+            .filter(|p| p.private == port && p.proto == proto);
+
+        for port in ports {
+            let (target, port) = match (port.public, entry.target(name)) {
+                (Some(public), _) => (self.host.clone(), public),
+                (None, Some(target)) => (self.absolute(&target), port.private),
+                (None, None) => continue,
+            };
+
+            reply.add_answer(Record::SRV {
+                qname: question.qname.clone(),
+                class: QClass::INTERNET,
+                ttl: self.ttl(entry),
+                priority: 0,
+                weight: 0,
+                port,
+                target,
+            });
+        }
+    }
+}
+
+/// Name within the zone, or as is if it ends with a dot
+fn absolute(name: &str, zone: &[String]) -> QName {
+    if let Some(fqdn) = name.strip_suffix('.') {
+        return QName::new(fqdn.split('.').map(String::from).collect());
+    }
+
+    let mut labels: Vec<String> = name.split('.').map(String::from).collect();
+    labels.extend(zone.iter().cloned());
+    QName::new(labels)
+}
+
+/// Names of a label, comma separated, made relative to the zone where they belong to it.
+/// Names too long for the wire are skipped.
+fn label_names(value: &str, zone: &[String]) -> Vec<String> {
+    let suffix = format!(".{}.", zone.join("."));
+
+    value
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty() && name != ".")
+        .map(|name| match name.strip_suffix(&suffix) {
+            Some(relative) if !relative.is_empty() => String::from(relative),
+            _ => name,
+        })
+        .filter(|name| absolute(name, zone).is_valid())
+        .collect()
+}
+
+/// Address of a `in-addr.arpa.` or `ip6.arpa.` name
+fn reverse_addr(qname: &QName) -> Option<IpAddr> {
+    let labels: Vec<String> = qname
+        .labels()
+        .iter()
+        .filter(|label| !label.is_empty())
+        .map(|label| label.to_lowercase())
+        .rev()
+        .collect();
+
+    match labels.iter().map(|l| l.as_str()).collect::<Vec<&str>>()[..] {
+        ["arpa", "in-addr", ref octets @ ..] if octets.len() == 4 => {
+            let mut addr = [0u8; 4];
+            for (idx, octet) in octets.iter().enumerate() {
+                addr[idx] = octet.parse().ok()?;
+            }
+            Some(IpAddr::V4(Ipv4Addr::from(addr)))
+        }
+        ["arpa", "ip6", ref nibbles @ ..] if nibbles.len() == 32 => {
+            let mut addr = 0u128;
+            for nibble in nibbles {
+                if nibble.len() != 1 {
+                    return None;
+                }
+                addr = (addr << 4) | u128::from_str_radix(nibble, 16).ok()?;
+            }
+            Some(IpAddr::V6(Ipv6Addr::from(addr)))
+        }
+        _ => None,
+    }
+}
+
+/// Port of a `_service` label, either a number or a well-known service name
+fn service_port(service: &str) -> Option<u16> {
+    match service {
+        "ftp" => Some(21),
+        "ssh" => Some(22),
+        "smtp" => Some(25),
+        "domain" => Some(53),
+        "http" => Some(80),
+        "https" => Some(443),
+        "mysql" => Some(3306),
+        "postgresql" => Some(5432),
+        "amqp" => Some(5672),
+        "redis" => Some(6379),
+        "mongodb" => Some(27017),
+        _ => service.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::model;
+    use crate::support::Parser;
+    use std::convert::TryFrom;
+
+    const CONTAINERS: &str = r#"[{
+        "Id": "8dfafdbc3a40",
+        "Names": ["/web", "/proxy/web"],
+        "State": "running",
+        "NetworkSettings": {"Networks": {
+            "bridge": {"IPAddress": "172.17.0.2", "GlobalIPv6Address": ""},
+            "dualstack": {"IPAddress": "172.18.0.2", "GlobalIPv6Address": "2001:db8:1::2"}
+        }},
+        "Ports": [
+            {"IP": "0.0.0.0", "PrivatePort": 80, "PublicPort": 8080, "Type": "tcp"},
+            {"IP": "::", "PrivatePort": 80, "PublicPort": 8080, "Type": "tcp"},
+            {"PrivatePort": 9000, "Type": "tcp"}
+        ]
+    }]"#;
+
+    fn resolver() -> Resolver {
+        let mut resolver = Resolver::new("docker.");
+        resolver.load(
+            &model::list(&Parser::parse(CONTAINERS.as_bytes().to_vec()).unwrap())
+                .unwrap()
+                .0,
+        );
+        resolver
+    }
+
+    fn query(qname: &str, qtype: QType) -> Message {
+        let mut msg = Message::new();
+        msg.ask(Question::new(QName::from_str(qname), qtype, None));
         msg
     }
+
+    #[test]
+    fn test_resolve_a() {
+        let reply = resolver().resolve(query("Web.docker.", QType::A));
+
+        assert_eq!(Rcode::NOERROR, reply.header().rcode());
+        assert!(reply.header().qr());
+        assert!(reply.header().aa());
+        assert_eq!(1, reply.questions().len());
+        assert_eq!(
+            &vec![
+                Record::A {
+                    qname: QName::from_str("Web.docker."),
+                    class: QClass::INTERNET,
+                    ttl: DEFAULT_TTL,
+                    addr: Ipv4Addr::new(172, 17, 0, 2),
+                },
+                Record::A {
+                    qname: QName::from_str("Web.docker."),
+                    class: QClass::INTERNET,
+                    ttl: DEFAULT_TTL,
+                    addr: Ipv4Addr::new(172, 18, 0, 2),
+                }
+            ],
+            reply.answers()
+        );
+    }
+
+    #[test]
+    fn test_resolve_aaaa() {
+        let reply = resolver().resolve(query("web.docker.", QType::AAAA));
+
+        assert_eq!(Rcode::NOERROR, reply.header().rcode());
+        assert_eq!(
+            &vec![Record::AAAA {
+                qname: QName::from_str("web.docker."),
+                class: QClass::INTERNET,
+                ttl: DEFAULT_TTL,
+                addr: "2001:db8:1::2".parse().unwrap(),
+            }],
+            reply.answers()
+        );
+    }
+
+    #[test]
+    fn test_resolve_srv() {
+        let reply = resolver().resolve(query("_http._tcp.web.docker.", QType::SRV));
+
+        assert_eq!(Rcode::NOERROR, reply.header().rcode());
+        assert_eq!(
+            &vec![Record::SRV {
+                qname: QName::from_str("_http._tcp.web.docker."),
+                class: QClass::INTERNET,
+                ttl: DEFAULT_TTL,
+                priority: 0,
+                weight: 0,
+                port: 8080,
+                target: QName::from_str(DEFAULT_HOST),
+            }],
+            reply.answers()
+        );
+
+        let reply = resolver().resolve(query("_9000._tcp.web.docker.", QType::SRV));
+
+        assert_eq!(
+            &vec![Record::SRV {
+                qname: QName::from_str("_9000._tcp.web.docker."),
+                class: QClass::INTERNET,
+                ttl: DEFAULT_TTL,
+                priority: 0,
+                weight: 0,
+                port: 9000,
+                target: QName::from_str("web.docker"),
+            }],
+            reply.answers()
+        );
+
+        let reply = resolver().resolve(query("_http._udp.web.docker.", QType::SRV));
+
+        assert_eq!(Rcode::NOERROR, reply.header().rcode());
+        assert!(reply.answers().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_ptr() {
+        let reply = resolver().resolve(query("2.0.18.172.in-addr.arpa.", QType::PTR));
+
+        assert_eq!(Rcode::NOERROR, reply.header().rcode());
+        assert_eq!(
+            &vec![Record::PTR {
+                qname: QName::from_str("2.0.18.172.in-addr.arpa."),
+                class: QClass::INTERNET,
+                ttl: DEFAULT_TTL,
+                ptrdname: QName::from_str("web.docker"),
+            }],
+            reply.answers()
+        );
+
+        let qname = "2.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.1.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.";
+        let reply = resolver().resolve(query(qname, QType::PTR));
+
+        assert_eq!(Rcode::NOERROR, reply.header().rcode());
+        assert_eq!(
+            &vec![Record::PTR {
+                qname: QName::from_str(qname),
+                class: QClass::INTERNET,
+                ttl: DEFAULT_TTL,
+                ptrdname: QName::from_str("web.docker"),
+            }],
+            reply.answers()
+        );
+
+        let reply = resolver().resolve(query("1.1.168.192.in-addr.arpa.", QType::PTR));
+
+        assert_eq!(Rcode::REFUSED, reply.header().rcode());
+        assert!(reply.answers().is_empty());
+    }
+
+    #[test]
+    fn test_update() {
+        let mut resolver = resolver();
+        let inspected = r#"{
+            "Id": "0b7a4b4a3c2e",
+            "Name": "/db",
+            "State": {"Running": true},
+            "NetworkSettings": {
+                "Ports": {"5432/tcp": [{"HostIp": "0.0.0.0", "HostPort": "15432"}]},
+                "Networks": {"bridge": {"IPAddress": "172.17.0.3"}}
+            }
+        }"#;
+        let inspected = Parser::parse(inspected.as_bytes().to_vec()).unwrap();
+        resolver.update(&Container::try_from(&inspected).unwrap());
+
+        let reply = resolver.resolve(query("db.docker.", QType::A));
+        assert_eq!(
+            &vec![Record::A {
+                qname: QName::from_str("db.docker."),
+                class: QClass::INTERNET,
+                ttl: DEFAULT_TTL,
+                addr: Ipv4Addr::new(172, 17, 0, 3),
+            }],
+            reply.answers()
+        );
+
+        let reply = resolver.resolve(query("_postgresql._tcp.db.docker.", QType::SRV));
+        assert_eq!(1, reply.answers().len());
+
+        resolver.remove("0b7a4b4a3c2e");
+
+        let reply = resolver.resolve(query("db.docker.", QType::A));
+        assert_eq!(Rcode::NXDOMAIN, reply.header().rcode());
+    }
+
+    #[test]
+    fn test_resolve_compose() {
+        let replicas = r#"[{
+            "Id": "3f1c4e2a9b70",
+            "Names": ["/shop_api_1"],
+            "Labels": {
+                "com.docker.compose.project": "shop",
+                "com.docker.compose.service": "api",
+                "com.docker.compose.container-number": "1"
+            },
+            "State": "running",
+            "NetworkSettings": {"Networks": {"shop_default": {"IPAddress": "172.20.0.3"}}},
+            "Ports": [{"PrivatePort": 8000, "Type": "tcp"}]
+        }, {
+            "Id": "a2d97c0e5f13",
+            "Names": ["/shop_api_2"],
+            "Labels": {
+                "com.docker.compose.project": "shop",
+                "com.docker.compose.service": "api",
+                "com.docker.compose.container-number": "2"
+            },
+            "State": "running",
+            "NetworkSettings": {"Networks": {"shop_default": {"IPAddress": "172.20.0.2"}}},
+            "Ports": [{"PrivatePort": 8000, "Type": "tcp"}]
+        }]"#;
+        let mut resolver = Resolver::new("docker.");
+        resolver.load(
+            &model::list(&Parser::parse(replicas.as_bytes().to_vec()).unwrap())
+                .unwrap()
+                .0,
+        );
+
+        let addrs = |reply: &Message| -> Vec<Ipv4Addr> {
+            reply
+                .answers()
+                .iter()
+                .filter_map(|record| match record {
+                    Record::A { addr, .. } => Some(*addr),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let reply = resolver.resolve(query("api.shop.docker.", QType::A));
+        assert_eq!(
+            vec![Ipv4Addr::new(172, 20, 0, 2), Ipv4Addr::new(172, 20, 0, 3)],
+            addrs(&reply)
+        );
+
+        let reply = resolver.resolve(query("2.api.shop.docker.", QType::A));
+        assert_eq!(vec![Ipv4Addr::new(172, 20, 0, 2)], addrs(&reply));
+
+        let reply = resolver.resolve(query("_8000._tcp.api.shop.docker.", QType::SRV));
+        assert_eq!(
+            &vec![
+                Record::SRV {
+                    qname: QName::from_str("_8000._tcp.api.shop.docker."),
+                    class: QClass::INTERNET,
+                    ttl: DEFAULT_TTL,
+                    priority: 0,
+                    weight: 0,
+                    port: 8000,
+                    target: QName::from_str("shop_api_1.docker"),
+                },
+                Record::SRV {
+                    qname: QName::from_str("_8000._tcp.api.shop.docker."),
+                    class: QClass::INTERNET,
+                    ttl: DEFAULT_TTL,
+                    priority: 0,
+                    weight: 0,
+                    port: 8000,
+                    target: QName::from_str("shop_api_2.docker"),
+                }
+            ],
+            reply.answers()
+        );
+
+        let reply = resolver.resolve(query("3.api.shop.docker.", QType::A));
+        assert_eq!(Rcode::NXDOMAIN, reply.header().rcode());
+
+        // the project only has names below it
+        let reply = resolver.resolve(query("shop.docker.", QType::A));
+        assert_eq!(Rcode::NOERROR, reply.header().rcode());
+        assert!(reply.header().aa());
+        assert!(reply.answers().is_empty());
+
+        let reply = resolver.resolve(query("hop.docker.", QType::A));
+        assert_eq!(Rcode::NXDOMAIN, reply.header().rcode());
+    }
+
+    #[test]
+    fn test_resolve_network_scoped() {
+        let mut resolver = resolver();
+        let inspected = r#"{
+            "Id": "0b7a4b4a3c2e",
+            "Name": "/db",
+            "State": {"Status": "running"},
+            "NetworkSettings": {"Networks": {
+                "frontend": {"Aliases": ["0b7a4b4a3c2e"], "IPAddress": "172.21.0.5"},
+                "backend": {"Aliases": ["db", "postgres", "DB"], "IPAddress": "172.22.0.5"}
+            }}
+        }"#;
+        let inspected = Parser::parse(inspected.as_bytes().to_vec()).unwrap();
+        resolver.update(&Container::try_from(&inspected).unwrap());
+
+        let reply = resolver.resolve(query("db.backend.docker.", QType::A));
+        assert_eq!(
+            &vec![Record::A {
+                qname: QName::from_str("db.backend.docker."),
+                class: QClass::INTERNET,
+                ttl: DEFAULT_TTL,
+                addr: Ipv4Addr::new(172, 22, 0, 5),
+            }],
+            reply.answers()
+        );
+
+        let reply = resolver.resolve(query("postgres.backend.docker.", QType::A));
+        assert_eq!(1, reply.answers().len());
+
+        // the name doubles as an alias, in any case
+        let scope = &resolver.entries["0b7a4b4a3c2e"].scopes[0];
+        assert_eq!(
+            vec![String::from("db.backend"), String::from("postgres.backend")],
+            scope.names
+        );
+
+        let reply = resolver.resolve(query("db.docker.", QType::A));
+        assert_eq!(2, reply.answers().len());
+
+        // aliases only exist on their own network
+        let reply = resolver.resolve(query("postgres.frontend.docker.", QType::A));
+        assert_eq!(Rcode::NXDOMAIN, reply.header().rcode());
+
+        let reply = resolver.resolve(query("web.dualstack.docker.", QType::AAAA));
+        assert_eq!(
+            &vec![Record::AAAA {
+                qname: QName::from_str("web.dualstack.docker."),
+                class: QClass::INTERNET,
+                ttl: DEFAULT_TTL,
+                addr: "2001:db8:1::2".parse().unwrap(),
+            }],
+            reply.answers()
+        );
+
+        let reply = resolver.resolve(query("_9000._tcp.web.bridge.docker.", QType::SRV));
+        match reply.answers().first() {
+            Some(Record::SRV { target, .. }) => {
+                assert_eq!(&QName::from_str("web.bridge.docker"), target)
+            }
+            other => panic!("Expected SRV record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_labels() {
+        let mut resolver = resolver();
+        resolver.set_foreign_suffixes(&["Local."]);
+        let inspected = r#"{
+            "Id": "5e8d1f0c7a21",
+            "Name": "/api",
+            "State": {"Status": "running"},
+            "Config": {"Labels": {
+                "dhns.name": "v1.api, api.local., google.com.",
+                "dhns.cname": "www.docker.",
+                "dhns.txt": "owner=payments",
+                "dhns.ttl": "30"
+            }},
+            "NetworkSettings": {"Networks": {"bridge": {"IPAddress": "172.17.0.4"}}}
+        }"#;
+        let inspected = Parser::parse(inspected.as_bytes().to_vec()).unwrap();
+        resolver.update(&Container::try_from(&inspected).unwrap());
+
+        let a = |qname: &str| Record::A {
+            qname: QName::from_str(qname),
+            class: QClass::INTERNET,
+            ttl: 30,
+            addr: Ipv4Addr::new(172, 17, 0, 4),
+        };
+
+        let reply = resolver.resolve(query("v1.api.docker.", QType::A));
+        assert_eq!(&vec![a("v1.api.docker.")], reply.answers());
+
+        let reply = resolver.resolve(query("api.local.", QType::A));
+        assert_eq!(Rcode::NOERROR, reply.header().rcode());
+        assert_eq!(&vec![a("api.local.")], reply.answers());
+
+        let reply = resolver.resolve(query("www.docker.", QType::A));
+        assert_eq!(
+            &vec![
+                Record::CNAME {
+                    qname: QName::from_str("www.docker."),
+                    class: QClass::INTERNET,
+                    ttl: 30,
+                    cname: QName::from_str("api.docker"),
+                },
+                Record::A {
+                    qname: QName::from_str("api.docker"),
+                    class: QClass::INTERNET,
+                    ttl: 30,
+                    addr: Ipv4Addr::new(172, 17, 0, 4),
+                }
+            ],
+            reply.answers()
+        );
+
+        let reply = resolver.resolve(query("api.docker.", QType::TXT));
+        assert_eq!(
+            &vec![Record::TXT {
+                qname: QName::from_str("api.docker."),
+                class: QClass::INTERNET,
+                ttl: 30,
+                data: vec![b"owner=payments".to_vec()],
+            }],
+            reply.answers()
+        );
+
+        let reply = resolver.resolve(query("other.local.", QType::A));
+        assert_eq!(Rcode::REFUSED, reply.header().rcode());
+
+        // declared, but not under an allowed suffix
+        let reply = resolver.resolve(query("google.com.", QType::A));
+        assert_eq!(Rcode::REFUSED, reply.header().rcode());
+        assert!(!resolver.is_authoritative(&query("google.com.", QType::A)));
+
+        resolver.set_foreign_suffixes(&[]);
+        let reply = resolver.resolve(query("api.local.", QType::A));
+        assert_eq!(Rcode::REFUSED, reply.header().rcode());
+    }
+
+    #[test]
+    fn test_long_names_skipped() {
+        let long = "a".repeat(64);
+        let inspected = format!(
+            r#"{{
+            "Id": "5e8d1f0c7a21",
+            "Name": "/{}",
+            "State": {{"Status": "running"}},
+            "Config": {{"Labels": {{"dhns.name": "api"}}}},
+            "NetworkSettings": {{"Networks": {{"bridge": {{"IPAddress": "172.17.0.4"}}}}}}
+        }}"#,
+            long
+        );
+        let inspected = Parser::parse(inspected.into_bytes()).unwrap();
+        let entry = Entry::new(
+            &Container::try_from(&inspected).unwrap(),
+            &[String::from("docker")],
+        );
+
+        assert_eq!(vec!["api"], entry.names);
+        assert!(entry.scopes[0].names.is_empty());
+    }
+
+    #[test]
+    fn test_label_names() {
+        let zone = vec![String::from("docker")];
+
+        assert_eq!(
+            vec!["api", "api.local.", "v1.api"],
+            label_names(" api , api.local., V1.api.docker., .", &zone)
+        );
+
+        let long = "a".repeat(64);
+        assert_eq!(
+            vec!["api"],
+            label_names(&format!("{}, api, {}.local.", long, long), &zone)
+        );
+    }
+
+    #[test]
+    fn test_is_authoritative() {
+        let resolver = resolver();
+
+        assert!(resolver.is_authoritative(&query("nothing.docker.", QType::A)));
+        assert!(resolver.is_authoritative(&query("2.0.17.172.in-addr.arpa.", QType::PTR)));
+        assert!(!resolver.is_authoritative(&query("8.8.8.8.in-addr.arpa.", QType::PTR)));
+        assert!(!resolver.is_authoritative(&query("example.com.", QType::A)));
+    }
+
+    #[test]
+    fn test_resolve_nxdomain() {
+        let reply = resolver().resolve(query("db.docker.", QType::A));
+
+        assert_eq!(Rcode::NXDOMAIN, reply.header().rcode());
+        assert!(reply.answers().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_refused() {
+        let reply = resolver().resolve(query("web.example.com.", QType::A));
+
+        assert_eq!(Rcode::REFUSED, reply.header().rcode());
+        assert!(!reply.header().aa());
+        assert!(reply.answers().is_empty());
+    }
 }
@@ -0,0 +1,358 @@
+use crate::docker::error::{DockerErr, DockerResult};
+use crate::docker::events::EventStream;
+use crate::docker::model::{self, Container, Network};
+use crate::docker::transport::Transport;
+use crate::support::{write_request, JsVal, Parser, Response};
+use std::convert::TryFrom;
+use std::io::BufReader;
+
+/// API version every supported engine understands
+pub const DEFAULT_VERSION: &str = "1.24";
+
+/// Newest API version whose responses we understand
+pub const MAX_VERSION: &str = "1.41";
+
+/// `{"type":["container","network"]}`
+const EVENT_FILTERS: &str = "%7B%22type%22%3A%5B%22container%22%2C%22network%22%5D%7D";
+
+/// Docker Engine API client.
+///
+/// Requests share a single keep-alive connection,
+/// except for the events stream which gets one of its own.
+pub struct Client<T: Transport> {
+    transport: T,
+    version: String,
+    base_path: String,
+    conn: Option<BufReader<T::Stream>>,
+}
+
+impl<T> Client<T>
+where
+    T: Transport,
+{
+    pub fn new(transport: T) -> Client<T> {
+        Client {
+            transport,
+            version: String::from(DEFAULT_VERSION),
+            base_path: String::new(),
+            conn: None,
+        }
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Sets API version requests are made with, e.g. `1.41`
+    pub fn set_version(&mut self, version: &str) {
+        self.version = String::from(version);
+    }
+
+    /// Sets path the API is served under, for engines behind a proxy
+    pub fn set_base_path(&mut self, base_path: &str) {
+        self.base_path = String::from(base_path.trim_end_matches('/'));
+    }
+
+    /// `GET /_ping`
+    pub fn ping(&mut self) -> DockerResult<bool> {
+        let path = format!("{}/_ping", self.base_path);
+        Ok(self.request(&path)?.trim() == "OK")
+    }
+
+    /// `GET /version`, unversioned so that it works whatever the engine supports
+    pub fn engine_version(&mut self) -> DockerResult<JsVal> {
+        let path = format!("{}/version", self.base_path);
+        self.get_json(&path)
+    }
+
+    /// Switches to the newest API version both the engine and dhns support,
+    /// keeping the current one if the engine doesn't tell.
+    /// Fails for engines older than `DEFAULT_VERSION`, which would reject our requests.
+    pub fn negotiate_version(&mut self) -> DockerResult<&str> {
+        let engine = match self.engine_version() {
+            Ok(info) => info
+                .get("ApiVersion")
+                .and_then(|version| version.as_str())
+                .and_then(parse_version),
+            // engines behind a restrictive proxy may not expose it
+            Err(DockerErr::Api { .. }) => None,
+            Err(err) => return Err(err),
+        };
+
+        let (min, max) = (parse_version(DEFAULT_VERSION), parse_version(MAX_VERSION));
+        if let (Some(engine), Some(min), Some(max)) = (engine, min, max) {
+            if engine < min {
+                return Err(DockerErr::OldEngine(format!("{}.{}", engine.0, engine.1)));
+            }
+
+            let (major, minor) = engine.min(max);
+            self.version = format!("{}.{}", major, minor);
+        }
+
+        Ok(&self.version)
+    }
+
+    /// `GET /containers/json`, running containers only,
+    /// along with the errors of those that could not be decoded
+    pub fn containers_list(&mut self) -> DockerResult<(Vec<Container>, Vec<DockerErr>)> {
+        let path = self.path("/containers/json");
+        model::list(&self.get_json(&path)?)
+    }
+
+    /// `GET /containers/{id}/json`
+    pub fn container_inspect(&mut self, id: &str) -> DockerResult<Container> {
+        let path = self.path(&format!("/containers/{}/json", id));
+        Container::try_from(&self.get_json(&path)?)
+    }
+
+    /// `GET /networks`, along with the errors of networks that could not be decoded
+    pub fn networks_list(&mut self) -> DockerResult<(Vec<Network>, Vec<DockerErr>)> {
+        let path = self.path("/networks");
+        model::list(&self.get_json(&path)?)
+    }
+
+    /// `GET /networks/{id}`
+    pub fn network_inspect(&mut self, id: &str) -> DockerResult<Network> {
+        let path = self.path(&format!("/networks/{}", id));
+        Network::try_from(&self.get_json(&path)?)
+    }
+
+    /// `GET /events` for containers and networks, streamed over a dedicated connection
+    pub fn events(&self) -> DockerResult<EventStream<T::Stream>> {
+        let path = self.path(&format!("/events?filters={}", EVENT_FILTERS));
+
+        let mut stream = self.transport.connect()?;
+        write_request(&mut stream, "GET", &path)?;
+
+        let mut response = Response::read(BufReader::new(stream))?;
+        if response.status() >= 400 {
+            let body = response.body()?;
+            return Err(Client::<T>::error(response.status(), body));
+        }
+
+        Ok(EventStream::new(response))
+    }
+
+    fn path(&self, endpoint: &str) -> String {
+        format!("{}/v{}{}", self.base_path, self.version, endpoint)
+    }
+
+    fn get_json(&mut self, path: &str) -> DockerResult<JsVal> {
+        let body = self.request(path)?;
+        Ok(Parser::parse(body.into_bytes())?)
+    }
+
+    /// GET request over the shared connection, returning the body
+    fn request(&mut self, path: &str) -> DockerResult<String> {
+        let reused = self.conn.is_some();
+
+        match self.try_request(path) {
+            // the engine may have closed an idle connection, retry once on a fresh one
+            Err(DockerErr::Io(_)) if reused => self.try_request(path),
+            result => result,
+        }
+    }
+
+    fn try_request(&mut self, path: &str) -> DockerResult<String> {
+        let mut conn = match self.conn.take() {
+            Some(conn) => conn,
+            None => BufReader::new(self.transport.connect()?),
+        };
+
+        write_request(conn.get_mut(), "GET", path)?;
+
+        let mut response = Response::read(&mut conn)?;
+        let body = response.body()?;
+        let status = response.status();
+
+        if response.keep_alive() {
+            self.conn = Some(conn);
+        }
+
+        if status >= 400 {
+            return Err(Client::<T>::error(status, body));
+        }
+
+        String::from_utf8(body)
+            .map_err(|err| DockerErr::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, err)))
+    }
+
+    /// Error from a response body, which carries `{"message": "..."}`
+    fn error(status: u16, body: Vec<u8>) -> DockerErr {
+        let message = match Parser::parse(body.clone()) {
+            Ok(JsVal::Object(obj)) => match obj.get("message") {
+                Some(JsVal::String(message)) => message.clone(),
+                _ => String::from_utf8_lossy(&body).into_owned(),
+            },
+            _ => String::from_utf8_lossy(&body).into_owned(),
+        };
+
+        DockerErr::Api { status, message }
+    }
+}
+
+/// `1.41` as `(1, 41)`, for comparing versions
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.splitn(2, '.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io::prelude::*;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    /// Replays canned responses and records requests
+    struct Stream {
+        response: Cursor<Vec<u8>>,
+        requests: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Read for Stream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.response.read(buf)
+        }
+    }
+
+    impl Write for Stream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.requests.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Canned {
+        responses: RefCell<Vec<&'static [u8]>>,
+        requests: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Transport for Canned {
+        type Stream = Stream;
+
+        fn connect(&self) -> std::io::Result<Stream> {
+            Ok(Stream {
+                response: Cursor::new(self.responses.borrow_mut().remove(0).to_vec()),
+                requests: Rc::clone(&self.requests),
+            })
+        }
+    }
+
+    fn client(responses: Vec<&'static [u8]>) -> (Client<Canned>, Rc<RefCell<Vec<u8>>>) {
+        let requests = Rc::new(RefCell::new(vec![]));
+        let transport = Canned {
+            responses: RefCell::new(responses),
+            requests: Rc::clone(&requests),
+        };
+
+        (Client::new(transport), requests)
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        let (mut client, requests) = client(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n[]\
+              HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK",
+        ]);
+
+        assert!(client.containers_list().unwrap().0.is_empty());
+        assert!(client.ping().unwrap());
+
+        let requests = String::from_utf8(requests.borrow().clone()).unwrap();
+        assert!(requests.starts_with("GET /v1.24/containers/json HTTP/1.1\r\n"));
+        assert!(requests.contains("GET /_ping HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let (mut client, _) = client(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n[]",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n[]",
+        ]);
+
+        assert!(client.containers_list().unwrap().0.is_empty());
+        assert!(client.networks_list().unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn test_api_error() {
+        let (mut client, _) = client(vec![
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 41\r\n\r\n{\"message\":\"No such container: 8dfafdbc\"}",
+        ]);
+
+        match client.container_inspect("8dfafdbc") {
+            Err(DockerErr::Api { status, message }) => {
+                assert_eq!(404, status);
+                assert_eq!("No such container: 8dfafdbc", message);
+            }
+            _ => panic!("Expected API error"),
+        }
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        let (mut client, requests) = client(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 22\r\n\r\n{\"ApiVersion\": \"1.38\"}\
+              HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n[]",
+        ]);
+
+        assert_eq!("1.38", client.negotiate_version().unwrap());
+        client.networks_list().unwrap();
+
+        let requests = String::from_utf8(requests.borrow().clone()).unwrap();
+        assert!(requests.starts_with("GET /version HTTP/1.1\r\n"));
+        assert!(requests.contains("GET /v1.38/networks HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_negotiate_newer_engine() {
+        let (mut client, _) = client(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 22\r\n\r\n{\"ApiVersion\": \"1.45\"}",
+        ]);
+
+        assert_eq!(MAX_VERSION, client.negotiate_version().unwrap());
+    }
+
+    #[test]
+    fn test_negotiate_older_engine() {
+        let (mut client, _) = client(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 22\r\n\r\n{\"ApiVersion\": \"1.12\"}",
+        ]);
+
+        match client.negotiate_version() {
+            Err(err @ DockerErr::OldEngine(_)) => assert_eq!(
+                "engine API 1.12 is older than the minimum 1.24",
+                err.to_string()
+            ),
+            other => panic!("Expected old engine error, got {:?}", other),
+        }
+        assert_eq!(DEFAULT_VERSION, client.version());
+    }
+
+    #[test]
+    fn test_negotiate_fallback() {
+        let (mut client, _) = client(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}\
+              HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+        ]);
+
+        assert_eq!(DEFAULT_VERSION, client.negotiate_version().unwrap());
+        assert_eq!(DEFAULT_VERSION, client.negotiate_version().unwrap());
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(Some((1, 41)), parse_version("1.41"));
+        assert!(parse_version("1.4.1").is_none());
+        assert!(parse_version("dev").is_none());
+        assert!((1, 9) < parse_version("1.24").unwrap());
+    }
+}
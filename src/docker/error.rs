@@ -0,0 +1,50 @@
+use crate::docker::client::DEFAULT_VERSION;
+use crate::support::JsErr;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum DockerErr {
+    Io(std::io::Error),
+    Json(JsErr),
+    /// Engine answered with an error status
+    Api {
+        status: u16,
+        message: String,
+    },
+    /// Response is missing a field or has one of the wrong type
+    Model(String),
+    /// Engine only speaks API versions older than `DEFAULT_VERSION`
+    OldEngine(String),
+}
+
+pub type DockerResult<T> = Result<T, DockerErr>;
+
+impl fmt::Display for DockerErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DockerErr::Io(err) => write!(f, "I/O error: {}", err),
+            DockerErr::Json(err) => write!(f, "{}", err),
+            DockerErr::Api { status, message } => write!(f, "HTTP {}: {}", status, message),
+            DockerErr::Model(message) => write!(f, "Unexpected response: {}", message),
+            DockerErr::OldEngine(version) => write!(
+                f,
+                "engine API {} is older than the minimum {}",
+                version, DEFAULT_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DockerErr {}
+
+impl From<std::io::Error> for DockerErr {
+    fn from(err: std::io::Error) -> DockerErr {
+        DockerErr::Io(err)
+    }
+}
+
+impl From<JsErr> for DockerErr {
+    fn from(err: JsErr) -> DockerErr {
+        DockerErr::Json(err)
+    }
+}
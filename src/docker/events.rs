@@ -0,0 +1,153 @@
+use crate::docker::error::{DockerErr, DockerResult};
+use crate::support::{JsVal, Parser, Response};
+use std::io::prelude::*;
+use std::io::BufReader;
+
+/// Container change that affects its names or addresses
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    /// Container started, was renamed or changed networks and has to be inspected
+    Update(String),
+    /// Container is gone
+    Remove(String),
+}
+
+impl Event {
+    pub fn from_js(event: &JsVal) -> Option<Event> {
+        let event = match event {
+            JsVal::Object(event) => event,
+            _ => return None,
+        };
+        let actor = match event.get("Actor") {
+            Some(JsVal::Object(actor)) => actor,
+            _ => return None,
+        };
+
+        match (event.get("Type"), event.get("Action")) {
+            (Some(JsVal::String(kind)), Some(JsVal::String(action))) if kind == "container" => {
+                let id = match actor.get("ID") {
+                    Some(JsVal::String(id)) => id.clone(),
+                    _ => return None,
+                };
+
+                match action.as_str() {
+                    "start" | "rename" => Some(Event::Update(id)),
+                    "die" | "stop" | "destroy" => Some(Event::Remove(id)),
+                    _ => None,
+                }
+            }
+            (Some(JsVal::String(kind)), Some(JsVal::String(action))) if kind == "network" => {
+                let id = match actor.get("Attributes") {
+                    Some(JsVal::Object(attributes)) => match attributes.get("container") {
+                        Some(JsVal::String(id)) => id.clone(),
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+
+                match action.as_str() {
+                    "connect" | "disconnect" => Some(Event::Update(id)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Stream of container events, one JSON object per line
+pub struct EventStream<T> {
+    lines: BufReader<Response<BufReader<T>>>,
+}
+
+impl<T> EventStream<T>
+where
+    T: Read,
+{
+    pub fn new(response: Response<BufReader<T>>) -> EventStream<T> {
+        EventStream {
+            lines: BufReader::new(response),
+        }
+    }
+}
+
+impl<T> Iterator for EventStream<T>
+where
+    T: Read,
+{
+    type Item = DockerResult<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut line = String::new();
+            match self.lines.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => {}
+                Err(err) => return Some(Err(DockerErr::Io(err))),
+            }
+
+            match Parser::parse(line.into_bytes()) {
+                Ok(event) => {
+                    if let Some(event) = Event::from_js(&event) {
+                        return Some(Ok(event));
+                    }
+                }
+                Err(err) => return Some(Err(DockerErr::Json(err))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(json: &str) -> Option<Event> {
+        Event::from_js(&Parser::parse(json.as_bytes().to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_container_events() {
+        assert_eq!(
+            Some(Event::Update(String::from("8dfafdbc3a40"))),
+            event(r#"{"Type": "container", "Action": "start", "Actor": {"ID": "8dfafdbc3a40"}}"#)
+        );
+        assert_eq!(
+            Some(Event::Remove(String::from("8dfafdbc3a40"))),
+            event(r#"{"Type": "container", "Action": "die", "Actor": {"ID": "8dfafdbc3a40"}}"#)
+        );
+        assert_eq!(
+            None,
+            event(r#"{"Type": "container", "Action": "create", "Actor": {"ID": "8dfafdbc3a40"}}"#)
+        );
+    }
+
+    #[test]
+    fn test_network_events() {
+        assert_eq!(
+            Some(Event::Update(String::from("8dfafdbc3a40"))),
+            event(
+                r#"{"Type": "network", "Action": "connect",
+                    "Actor": {"ID": "7d86d31b1478", "Attributes": {"container": "8dfafdbc3a40"}}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_stream() {
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            40\r\n{\"Type\": \"container\", \"Action\": \"create\", \"Actor\": {\"ID\": \"a\"}}\n\r\n\
+            3f\r\n{\"Type\": \"container\", \"Action\": \"start\", \"Actor\": {\"ID\": \"a\"}}\n\r\n\
+            0\r\n\r\n";
+        let response = Response::read(BufReader::new(&data[..])).unwrap();
+
+        let mut events = EventStream::new(response);
+
+        assert_eq!(
+            Some(Event::Update(String::from("a"))),
+            events.next().map(|e| e.unwrap())
+        );
+        assert!(events.next().is_none());
+    }
+}
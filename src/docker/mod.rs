@@ -1 +1,9 @@
-pub mod request;
+pub mod client;
+pub mod error;
+pub mod events;
+pub mod model;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod transport;
+
+pub use client::Client;
@@ -0,0 +1,325 @@
+use super::{labels, opt_string, parsed, string, strings};
+use crate::docker::error::{DockerErr, DockerResult};
+use crate::support::JsVal;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Container, from either `GET /containers/json` or `GET /containers/{id}/json`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Container {
+    pub id: String,
+    /// Names without the leading `/`, links excluded
+    pub names: Vec<String>,
+    pub labels: HashMap<String, String>,
+    /// `created`, `running`, `exited`, ...
+    pub state: String,
+    /// Endpoints keyed by network name
+    pub networks: HashMap<String, Endpoint>,
+    pub ports: Vec<Port>,
+}
+
+/// Container attachment to a network
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    pub network_id: String,
+    pub aliases: Vec<String>,
+    pub ipv4: Option<Ipv4Addr>,
+    /// Only set on networks with IPv6 enabled
+    pub ipv6: Option<Ipv6Addr>,
+}
+
+/// Port exposed by a container
+#[derive(Debug, Clone, PartialEq)]
+pub struct Port {
+    pub private: u16,
+    /// Port on the Docker host, if published
+    pub public: Option<u16>,
+    /// `tcp`, `udp` or `sctp`
+    pub proto: String,
+}
+
+impl Container {
+    pub fn running(&self) -> bool {
+        self.state == "running"
+    }
+
+    fn add_name(&mut self, name: &str) {
+        // links are listed as `/other/alias`, skip them
+        let name = name.trim_start_matches('/');
+        if !name.contains('/') {
+            self.names.push(String::from(name));
+        }
+    }
+
+    fn add_port(&mut self, port: Port) {
+        // published ports are listed once per host address
+        if !self.ports.contains(&port) {
+            self.ports.push(port);
+        }
+    }
+
+    /// Ports of a list item, e.g. `[{"PrivatePort": 80, "PublicPort": 8080, "Type": "tcp"}]`
+    fn add_listed_ports(&mut self, ports: &[JsVal]) -> DockerResult<()> {
+        for port in ports {
+            let private = match port.get("PrivatePort").and_then(|p| p.as_i64()) {
+                Some(private) => private as u16,
+                None => return Err(DockerErr::Model(String::from("missing `PrivatePort`"))),
+            };
+
+            self.add_port(Port {
+                private,
+                public: port
+                    .get("PublicPort")
+                    .and_then(|p| p.as_i64())
+                    .map(|p| p as u16),
+                proto: string(port, "Type")?.to_lowercase(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Ports of an inspected container, keyed as `80/tcp` with a list of host bindings if published
+    fn add_inspected_ports(&mut self, ports: &HashMap<String, JsVal>) -> DockerResult<()> {
+        for (key, bindings) in ports.iter() {
+            let mut parts = key.splitn(2, '/');
+            let private = match parts.next().and_then(|port| port.parse().ok()) {
+                Some(port) => port,
+                None => return Err(DockerErr::Model(format!("invalid port `{}`", key))),
+            };
+            let proto = parts.next().unwrap_or("tcp").to_lowercase();
+
+            let published: Vec<u16> = bindings
+                .as_array()
+                .map(|bindings| {
+                    bindings
+                        .iter()
+                        .filter_map(|binding| binding.get("HostPort")?.as_str()?.parse().ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            if published.is_empty() {
+                self.add_port(Port {
+                    private,
+                    public: None,
+                    proto,
+                });
+            } else {
+                for public in published {
+                    self.add_port(Port {
+                        private,
+                        public: Some(public),
+                        proto: proto.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl TryFrom<&JsVal> for Container {
+    type Error = DockerErr;
+
+    fn try_from(val: &JsVal) -> DockerResult<Container> {
+        let mut container = Container {
+            id: string(val, "Id")?,
+            names: vec![],
+            labels: HashMap::new(),
+            state: String::new(),
+            networks: HashMap::new(),
+            ports: vec![],
+        };
+
+        // list items carry `Names` and flat fields, inspected containers nest them
+        let listed = val.get("Names").is_some();
+
+        if listed {
+            for name in strings(val, "Names") {
+                container.add_name(&name);
+            }
+            container.labels = labels(val.get("Labels"));
+            container.state = string(val, "State")?;
+        } else {
+            container.add_name(&string(val, "Name")?);
+            container.labels = labels(val.get("Config").and_then(|config| config.get("Labels")));
+
+            let state = match val.get("State") {
+                Some(state) => state,
+                None => return Err(DockerErr::Model(String::from("missing `State`"))),
+            };
+            // `Status` appeared in API 1.21, derive it from `Running` before that
+            container.state = match opt_string(state, "Status") {
+                Some(status) => status,
+                None if state.get("Running").and_then(|r| r.as_bool()) == Some(true) => {
+                    String::from("running")
+                }
+                None => String::from("exited"),
+            };
+        }
+
+        let settings = val.get("NetworkSettings");
+
+        if let Some(networks) = settings
+            .and_then(|s| s.get("Networks"))
+            .and_then(|n| n.as_object())
+        {
+            for (name, network) in networks.iter() {
+                container
+                    .networks
+                    .insert(name.clone(), Endpoint::try_from(network)?);
+            }
+        }
+
+        if listed {
+            if let Some(ports) = val.get("Ports").and_then(|ports| ports.as_array()) {
+                container.add_listed_ports(ports)?;
+            }
+        } else if let Some(ports) = settings
+            .and_then(|s| s.get("Ports"))
+            .and_then(|p| p.as_object())
+        {
+            container.add_inspected_ports(ports)?;
+        }
+
+        Ok(container)
+    }
+}
+
+impl TryFrom<&JsVal> for Endpoint {
+    type Error = DockerErr;
+
+    fn try_from(val: &JsVal) -> DockerResult<Endpoint> {
+        Ok(Endpoint {
+            network_id: opt_string(val, "NetworkID").unwrap_or_default(),
+            aliases: strings(val, "Aliases"),
+            ipv4: parsed(val, "IPAddress")?,
+            ipv6: parsed(val, "GlobalIPv6Address")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::support::Parser;
+
+    fn parse(json: &str) -> JsVal {
+        Parser::parse(json.as_bytes().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_listed() {
+        let val = parse(
+            r#"{
+            "Id": "8dfafdbc3a40",
+            "Names": ["/web", "/proxy/web"],
+            "Labels": {"com.docker.compose.service": "web"},
+            "State": "running",
+            "NetworkSettings": {"Networks": {
+                "bridge": {"NetworkID": "7ea29fc1412b", "Aliases": null, "IPAddress": "172.17.0.2", "GlobalIPv6Address": ""}
+            }},
+            "Ports": [
+                {"IP": "0.0.0.0", "PrivatePort": 80, "PublicPort": 8080, "Type": "tcp"},
+                {"IP": "::", "PrivatePort": 80, "PublicPort": 8080, "Type": "tcp"},
+                {"PrivatePort": 53, "Type": "udp"}
+            ]
+        }"#,
+        );
+        let container = Container::try_from(&val).unwrap();
+
+        assert_eq!("8dfafdbc3a40", container.id);
+        assert_eq!(vec![String::from("web")], container.names);
+        assert_eq!(
+            Some(&String::from("web")),
+            container.labels.get("com.docker.compose.service")
+        );
+        assert!(container.running());
+        assert_eq!(
+            Endpoint {
+                network_id: String::from("7ea29fc1412b"),
+                aliases: vec![],
+                ipv4: Some(Ipv4Addr::new(172, 17, 0, 2)),
+                ipv6: None,
+            },
+            container.networks["bridge"]
+        );
+        assert_eq!(
+            vec![
+                Port {
+                    private: 80,
+                    public: Some(8080),
+                    proto: String::from("tcp"),
+                },
+                Port {
+                    private: 53,
+                    public: None,
+                    proto: String::from("udp"),
+                }
+            ],
+            container.ports
+        );
+    }
+
+    #[test]
+    fn test_inspected() {
+        let val = parse(
+            r#"{
+            "Id": "0b7a4b4a3c2e",
+            "Name": "/db",
+            "State": {"Status": "exited", "Running": false},
+            "Config": {"Labels": {"dhns.name": "postgres"}},
+            "NetworkSettings": {
+                "Ports": {"5432/tcp": [{"HostIp": "0.0.0.0", "HostPort": "15432"}], "8008/tcp": null},
+                "Networks": {"backend": {"Aliases": ["db", "0b7a4b4a3c2e"], "IPAddress": "172.18.0.3", "GlobalIPv6Address": "2001:db8:1::3"}}
+            }
+        }"#,
+        );
+        let container = Container::try_from(&val).unwrap();
+
+        assert_eq!(vec![String::from("db")], container.names);
+        assert_eq!(
+            Some(&String::from("postgres")),
+            container.labels.get("dhns.name")
+        );
+        assert!(!container.running());
+
+        let endpoint = &container.networks["backend"];
+        assert_eq!(
+            vec![String::from("db"), String::from("0b7a4b4a3c2e")],
+            endpoint.aliases
+        );
+        assert_eq!(
+            Some(Ipv6Addr::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 3)),
+            endpoint.ipv6
+        );
+
+        assert!(container.ports.contains(&Port {
+            private: 5432,
+            public: Some(15432),
+            proto: String::from("tcp"),
+        }));
+        assert!(container.ports.contains(&Port {
+            private: 8008,
+            public: None,
+            proto: String::from("tcp"),
+        }));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(
+            Container::try_from(&parse(r#"{"Names": ["/web"]}"#)),
+            Err(DockerErr::Model(_))
+        ));
+        assert!(matches!(
+            Container::try_from(&parse(
+                r#"{"Id": "8dfafdbc3a40", "Names": [], "State": "running", "NetworkSettings": {"Networks": {"bridge": {"IPAddress": "172.17.0"}}}}"#
+            )),
+            Err(DockerErr::Model(_))
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+mod container;
+mod network;
+
+pub use container::{Container, Endpoint, Port};
+pub use network::Network;
+
+use crate::docker::error::{DockerErr, DockerResult};
+use crate::support::JsVal;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Converts the items of a JSON array, along with the errors of those that don't decode,
+/// so one odd container doesn't hide all the others
+pub fn list<T>(val: &JsVal) -> DockerResult<(Vec<T>, Vec<DockerErr>)>
+where
+    T: for<'a> TryFrom<&'a JsVal, Error = DockerErr>,
+{
+    let items = match val.as_array() {
+        Some(items) => items,
+        None => return Err(DockerErr::Model(String::from("expected an array"))),
+    };
+
+    let mut decoded = vec![];
+    let mut skipped = vec![];
+    for item in items {
+        match T::try_from(item) {
+            Ok(item) => decoded.push(item),
+            Err(err) => skipped.push(err),
+        }
+    }
+
+    Ok((decoded, skipped))
+}
+
+/// Required string member of an object
+fn string(obj: &JsVal, key: &str) -> DockerResult<String> {
+    match obj.get(key).and_then(|val| val.as_str()) {
+        Some(s) => Ok(String::from(s)),
+        None => Err(DockerErr::Model(format!("missing or invalid `{}`", key))),
+    }
+}
+
+/// Optional string member, empty strings count as missing
+fn opt_string(obj: &JsVal, key: &str) -> Option<String> {
+    obj.get(key)
+        .and_then(|val| val.as_str())
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+}
+
+/// Optional string member parsed into e.g. an address, empty strings count as missing
+fn parsed<T: FromStr>(obj: &JsVal, key: &str) -> DockerResult<Option<T>> {
+    match opt_string(obj, key) {
+        Some(s) => match s.parse() {
+            Ok(val) => Ok(Some(val)),
+            Err(_) => Err(DockerErr::Model(format!("invalid `{}` {}", key, s))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Strings of an array member, which the engine sends as `null` when empty
+fn strings(obj: &JsVal, key: &str) -> Vec<String> {
+    obj.get(key)
+        .and_then(|val| val.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Label map, which the engine sends as `null` when empty
+fn labels(val: Option<&JsVal>) -> HashMap<String, String> {
+    val.and_then(|val| val.as_object())
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|(key, val)| Some((key.clone(), String::from(val.as_str()?))))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::support::Parser;
+
+    #[test]
+    fn test_list_skips_invalid() {
+        let json = r#"[
+            {"Id": "1", "Name": "frontend", "Driver": "bridge"},
+            {"Id": "2"},
+            {"Id": "3", "Name": "backend", "Driver": "overlay"}
+        ]"#;
+        let val = Parser::parse(json.as_bytes().to_vec()).unwrap();
+        let (networks, skipped): (Vec<Network>, _) = list(&val).unwrap();
+
+        assert_eq!(
+            vec!["frontend", "backend"],
+            networks.iter().map(|n| n.name.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(1, skipped.len());
+
+        let val = Parser::parse(b"{}".to_vec()).unwrap();
+        assert!(list::<Network>(&val).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use super::{labels, string};
+use crate::docker::error::{DockerErr, DockerResult};
+use crate::support::JsVal;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// Network, from either `GET /networks` or `GET /networks/{id}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Network {
+    pub id: String,
+    pub name: String,
+    /// `bridge`, `overlay`, `host`, ...
+    pub driver: String,
+    pub labels: HashMap<String, String>,
+    pub enable_ipv6: bool,
+    pub internal: bool,
+    /// IDs of attached containers, only filled in when inspecting a network
+    pub containers: Vec<String>,
+}
+
+impl TryFrom<&JsVal> for Network {
+    type Error = DockerErr;
+
+    fn try_from(val: &JsVal) -> DockerResult<Network> {
+        let mut containers: Vec<String> = val
+            .get("Containers")
+            .and_then(|containers| containers.as_object())
+            .map(|containers| containers.keys().cloned().collect())
+            .unwrap_or_default();
+        containers.sort();
+
+        Ok(Network {
+            id: string(val, "Id")?,
+            name: string(val, "Name")?,
+            driver: string(val, "Driver")?,
+            labels: labels(val.get("Labels")),
+            enable_ipv6: val
+                .get("EnableIPv6")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            internal: val
+                .get("Internal")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            containers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::support::Parser;
+
+    #[test]
+    fn test_network() {
+        let json = r#"{
+            "Name": "backend",
+            "Id": "7ea29fc1412b",
+            "Driver": "bridge",
+            "EnableIPv6": true,
+            "Labels": {"com.docker.compose.network": "backend"},
+            "Containers": {"0b7a4b4a3c2e": {"Name": "db", "IPv4Address": "172.18.0.3/16"}}
+        }"#;
+        let val = Parser::parse(json.as_bytes().to_vec()).unwrap();
+        let network = Network::try_from(&val).unwrap();
+
+        assert_eq!("7ea29fc1412b", network.id);
+        assert_eq!("backend", network.name);
+        assert_eq!("bridge", network.driver);
+        assert!(network.enable_ipv6);
+        assert!(!network.internal);
+        assert_eq!(vec![String::from("0b7a4b4a3c2e")], network.containers);
+    }
+
+    #[test]
+    fn test_missing_name() {
+        let val = Parser::parse(b"{\"Id\": \"7ea29fc1412b\"}".to_vec()).unwrap();
+
+        assert!(Network::try_from(&val).is_err());
+    }
+}
@@ -1 +0,0 @@
-pub mod containers_list;
@@ -0,0 +1,81 @@
+use crate::docker::transport::Transport;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Result};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::Arc;
+
+/// TCP with mutual TLS, as set up by `DOCKER_TLS_VERIFY` and `DOCKER_CERT_PATH`
+#[derive(Debug, Clone)]
+pub struct Tls {
+    host: String,
+    port: u16,
+    config: Arc<ClientConfig>,
+}
+
+impl Tls {
+    /// Loads `ca.pem`, `cert.pem` and `key.pem` from the certificate directory
+    pub fn new(host: &str, port: u16, cert_path: &Path) -> Result<Tls> {
+        let mut roots = RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut open(&cert_path.join("ca.pem"))?) {
+            roots.add(cert?).map_err(invalid)?;
+        }
+
+        let certs = rustls_pemfile::certs(&mut open(&cert_path.join("cert.pem"))?)
+            .collect::<Result<Vec<_>>>()?;
+        let key = match rustls_pemfile::private_key(&mut open(&cert_path.join("key.pem"))?)? {
+            Some(key) => key,
+            None => return Err(invalid("No private key in key.pem")),
+        };
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(invalid)?
+            .with_root_certificates(roots)
+            .with_client_auth_cert(certs, key)
+            .map_err(invalid)?;
+
+        Ok(Tls {
+            host: String::from(host),
+            port,
+            config: Arc::new(config),
+        })
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+}
+
+impl Transport for Tls {
+    type Stream = StreamOwned<ClientConnection, TcpStream>;
+
+    fn connect(&self) -> Result<Self::Stream> {
+        let name = ServerName::try_from(self.host.clone()).map_err(invalid)?;
+        let conn = ClientConnection::new(Arc::clone(&self.config), name).map_err(invalid)?;
+        let sock = TcpStream::connect((self.host.as_str(), self.port))?;
+
+        Ok(StreamOwned::new(conn, sock))
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+    match File::open(path) {
+        Ok(file) => Ok(BufReader::new(file)),
+        Err(err) => Err(Error::new(
+            err.kind(),
+            format!("{}: {}", path.display(), err),
+        )),
+    }
+}
+
+fn invalid<E>(err: E) -> Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    Error::new(ErrorKind::InvalidData, err)
+}
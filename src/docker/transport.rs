@@ -0,0 +1,271 @@
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "tls")]
+use crate::docker::tls::Tls;
+
+/// Socket used when `DOCKER_HOST` is not set
+pub const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+const DEFAULT_PORT: u16 = 2375;
+
+const DEFAULT_TLS_PORT: u16 = 2376;
+
+/// Opens connections to the Docker Engine
+pub trait Transport {
+    type Stream: Read + Write;
+
+    fn connect(&self) -> Result<Self::Stream>;
+}
+
+/// Unix domain socket, e.g. `/var/run/docker.sock`
+#[derive(Debug, Clone)]
+pub struct Unix {
+    path: PathBuf,
+}
+
+impl Unix {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Unix {
+        Unix { path: path.into() }
+    }
+}
+
+impl Transport for Unix {
+    type Stream = UnixStream;
+
+    fn connect(&self) -> Result<UnixStream> {
+        UnixStream::connect(&self.path)
+    }
+}
+
+/// Plain TCP, e.g. `tcp://docker.example.com:2375`
+#[derive(Debug, Clone)]
+pub struct Tcp {
+    host: String,
+    port: u16,
+}
+
+impl Tcp {
+    pub fn new(host: &str, port: u16) -> Tcp {
+        Tcp {
+            host: String::from(host),
+            port,
+        }
+    }
+}
+
+impl Transport for Tcp {
+    type Stream = TcpStream;
+
+    fn connect(&self) -> Result<TcpStream> {
+        TcpStream::connect((self.host.as_str(), self.port))
+    }
+}
+
+/// Transport chosen at runtime, usually from `DOCKER_HOST`
+#[derive(Debug, Clone)]
+pub enum AnyTransport {
+    Unix(Unix),
+    Tcp(Tcp),
+    #[cfg(feature = "tls")]
+    Tls(Tls),
+}
+
+/// Stream of an `AnyTransport`
+#[derive(Debug)]
+pub enum AnyStream {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<<Tls as Transport>::Stream>),
+}
+
+impl AnyTransport {
+    /// Transport for `DOCKER_HOST`, using TLS with the certificates
+    /// in `DOCKER_CERT_PATH` if `DOCKER_TLS_VERIFY` is set
+    pub fn from_env() -> Result<AnyTransport> {
+        let host = std::env::var("DOCKER_HOST")
+            .ok()
+            .filter(|host| !host.is_empty())
+            .unwrap_or_else(|| format!("unix://{}", DEFAULT_SOCKET));
+
+        let verify = std::env::var("DOCKER_TLS_VERIFY").is_ok_and(|verify| !verify.is_empty());
+        let cert_path = if verify {
+            let path = match std::env::var("DOCKER_CERT_PATH") {
+                Ok(path) if !path.is_empty() => PathBuf::from(path),
+                _ => match std::env::var("HOME") {
+                    Ok(home) => Path::new(&home).join(".docker"),
+                    Err(_) => return Err(invalid("DOCKER_CERT_PATH is not set")),
+                },
+            };
+            Some(path)
+        } else {
+            None
+        };
+
+        AnyTransport::parse(&host, cert_path.as_deref())
+    }
+
+    /// Transport for a `unix://` or `tcp://` address,
+    /// with TLS if a directory with `ca.pem`, `cert.pem` and `key.pem` is given
+    pub fn parse(host: &str, cert_path: Option<&Path>) -> Result<AnyTransport> {
+        if let Some(path) = host.strip_prefix("unix://") {
+            return Ok(AnyTransport::Unix(Unix::new(path)));
+        }
+
+        let addr = match host.strip_prefix("tcp://") {
+            Some(addr) => addr.trim_end_matches('/'),
+            None => return Err(invalid(&format!("Unsupported DOCKER_HOST {}", host))),
+        };
+
+        let default_port = match cert_path {
+            Some(_) => DEFAULT_TLS_PORT,
+            None => DEFAULT_PORT,
+        };
+        let (host, port) = split_host_port(addr, default_port)?;
+
+        match cert_path {
+            None => Ok(AnyTransport::Tcp(Tcp::new(host, port))),
+            #[cfg(feature = "tls")]
+            Some(cert_path) => Ok(AnyTransport::Tls(Tls::new(host, port, cert_path)?)),
+            #[cfg(not(feature = "tls"))]
+            Some(_) => Err(invalid(
+                "TLS requested but dhns was built without the `tls` feature",
+            )),
+        }
+    }
+
+    /// Name of a remote engine, where published ports are reachable
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            AnyTransport::Unix(_) => None,
+            AnyTransport::Tcp(tcp) => Some(&tcp.host),
+            #[cfg(feature = "tls")]
+            AnyTransport::Tls(tls) => Some(tls.host()),
+        }
+    }
+}
+
+impl Transport for AnyTransport {
+    type Stream = AnyStream;
+
+    fn connect(&self) -> Result<AnyStream> {
+        match self {
+            AnyTransport::Unix(unix) => Ok(AnyStream::Unix(unix.connect()?)),
+            AnyTransport::Tcp(tcp) => Ok(AnyStream::Tcp(tcp.connect()?)),
+            #[cfg(feature = "tls")]
+            AnyTransport::Tls(tls) => Ok(AnyStream::Tls(Box::new(tls.connect()?))),
+        }
+    }
+}
+
+impl Read for AnyStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            AnyStream::Unix(stream) => stream.read(buf),
+            AnyStream::Tcp(stream) => stream.read(buf),
+            #[cfg(feature = "tls")]
+            AnyStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for AnyStream {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            AnyStream::Unix(stream) => stream.write(buf),
+            AnyStream::Tcp(stream) => stream.write(buf),
+            #[cfg(feature = "tls")]
+            AnyStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            AnyStream::Unix(stream) => stream.flush(),
+            AnyStream::Tcp(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            AnyStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+/// Splits `host:port`, `[::1]:port` or a bare host
+fn split_host_port(addr: &str, default_port: u16) -> Result<(&str, u16)> {
+    let (host, port) = match addr.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((host, port)) => (host, port.strip_prefix(':')),
+            None => return Err(invalid(&format!("Invalid address {}", addr))),
+        },
+        None => match addr.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (addr, None),
+        },
+    };
+
+    if host.is_empty() {
+        return Err(invalid(&format!("Missing host in {}", addr)));
+    }
+
+    match port {
+        Some(port) => match port.parse() {
+            Ok(port) => Ok((host, port)),
+            Err(_) => Err(invalid(&format!("Invalid port in {}", addr))),
+        },
+        None => Ok((host, default_port)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_unix() {
+        match AnyTransport::parse("unix:///run/user/1000/docker.sock", None) {
+            Ok(AnyTransport::Unix(unix)) => {
+                assert_eq!(Path::new("/run/user/1000/docker.sock"), unix.path)
+            }
+            other => panic!("Expected Unix transport, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_tcp() {
+        match AnyTransport::parse("tcp://docker.example.com:2375", None) {
+            Ok(AnyTransport::Tcp(tcp)) => {
+                assert_eq!("docker.example.com", tcp.host);
+                assert_eq!(2375, tcp.port);
+            }
+            other => panic!("Expected TCP transport, got {:?}", other),
+        }
+
+        let transport = AnyTransport::parse("tcp://[fd00::1]", None).unwrap();
+        assert_eq!(Some("fd00::1"), transport.host());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(AnyTransport::parse("ssh://docker.example.com", None).is_err());
+        assert!(AnyTransport::parse("tcp://:2375", None).is_err());
+        assert!(AnyTransport::parse("tcp://docker.example.com:http", None).is_err());
+        assert!(AnyTransport::parse("tcp://localhost", Some(Path::new("/nonexistent"))).is_err());
+    }
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(
+            ("10.0.0.1", 2376),
+            split_host_port("10.0.0.1:2376", 2375).unwrap()
+        );
+        assert_eq!(("::1", 2375), split_host_port("[::1]", 2375).unwrap());
+        assert_eq!(("::1", 2376), split_host_port("[::1]:2376", 2375).unwrap());
+    }
+}
@@ -5,6 +5,12 @@ pub struct HeadersBag {
     headers: HashMap<String, Vec<String>>,
 }
 
+impl Default for HeadersBag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HeadersBag {
     pub fn new() -> HeadersBag {
         HeadersBag {
@@ -12,6 +18,7 @@ impl HeadersBag {
         }
     }
 
+    /// Adds `Name: value` header, lines without a colon are ignored
     pub fn add_from_string(&mut self, header: String) {
         if let Some(pos) = header.find(':') {
             let name = &header[..pos].to_lowercase();
@@ -22,16 +29,12 @@ impl HeadersBag {
                     self.headers.insert(String::from(name), vec![value]);
                 }
             }
-        } else {
-            unimplemented!()
         }
     }
 
     pub fn get_first(&self, header: String) -> Option<String> {
-        if let Some(headers) = self.headers.get(&header) {
-            Some(String::from(&headers[0]))
-        } else {
-            None
-        }
+        self.headers
+            .get(&header)
+            .map(|headers| String::from(&headers[0]))
     }
 }
@@ -0,0 +1,290 @@
+use super::HeadersBag;
+use std::io::prelude::*;
+use std::io::{Error, ErrorKind, Result};
+
+/// How the end of the body is determined
+#[derive(Debug)]
+enum Framing {
+    /// `Content-Length` bytes left to read
+    Length(usize),
+    /// `Transfer-Encoding: chunked`, with bytes left in the current chunk
+    Chunked { remaining: usize, done: bool },
+    /// Body ends when the connection is closed
+    Close,
+}
+
+/// HTTP/1.x response, reading from it yields the decoded body.
+///
+/// Once the body has been read completely the underlying reader is positioned
+/// at the start of the next response, so it can be reused if `keep_alive()`.
+#[derive(Debug)]
+pub struct Response<R> {
+    reader: R,
+    version: String,
+    status: u16,
+    headers: HeadersBag,
+    trailers: HeadersBag,
+    framing: Framing,
+}
+
+/// Writes a bodiless HTTP/1.1 request
+pub fn write_request<W: Write>(io: &mut W, method: &str, path: &str) -> Result<()> {
+    write!(io, "{} {} HTTP/1.1\r\n", method, path)?;
+    io.write_all(b"Host: localhost\r\n")?;
+    io.write_all(b"\r\n")?;
+    io.flush()
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "connection closed"));
+    }
+
+    Ok(line.trim().to_string())
+}
+
+/// Reads header lines up to and including the empty line
+fn read_headers<R: BufRead>(reader: &mut R, headers: &mut HeadersBag) -> Result<()> {
+    loop {
+        let header = read_line(reader)?;
+
+        if header.is_empty() {
+            return Ok(());
+        }
+
+        headers.add_from_string(header);
+    }
+}
+
+impl<R> Response<R>
+where
+    R: BufRead,
+{
+    /// Reads status line and headers
+    pub fn read(mut reader: R) -> Result<Response<R>> {
+        let status_line = read_line(&mut reader)?;
+        let mut parts = status_line.splitn(3, ' ');
+
+        let version = match parts.next() {
+            Some(version) if version.starts_with("HTTP/1.") => String::from(version),
+            _ => return Err(invalid("Invalid HTTP status line")),
+        };
+        let status = match parts.next().and_then(|status| status.parse().ok()) {
+            Some(status) => status,
+            None => return Err(invalid("Invalid HTTP status code")),
+        };
+
+        let mut headers = HeadersBag::new();
+        read_headers(&mut reader, &mut headers)?;
+
+        let chunked = headers
+            .get_first(String::from("transfer-encoding"))
+            .is_some_and(|encoding| encoding.to_lowercase().contains("chunked"));
+
+        let framing = if chunked {
+            Framing::Chunked {
+                remaining: 0,
+                done: false,
+            }
+        } else if let Some(length) = headers.get_first(String::from("content-length")) {
+            match length.parse() {
+                Ok(length) => Framing::Length(length),
+                Err(_) => return Err(invalid("Invalid Content-Length")),
+            }
+        } else if status == 204 || status == 304 || (100..200).contains(&status) {
+            Framing::Length(0)
+        } else {
+            Framing::Close
+        };
+
+        Ok(Response {
+            reader,
+            version,
+            status,
+            headers,
+            trailers: HeadersBag::new(),
+            framing,
+        })
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn headers(&self) -> &HeadersBag {
+        &self.headers
+    }
+
+    /// Trailer fields of a chunked body, available once it has been read
+    pub fn trailers(&self) -> &HeadersBag {
+        &self.trailers
+    }
+
+    /// Whether the connection can carry another request after this response
+    pub fn keep_alive(&self) -> bool {
+        if let Framing::Close = self.framing {
+            return false;
+        }
+
+        let connection = self
+            .headers
+            .get_first(String::from("connection"))
+            .map(|connection| connection.to_lowercase());
+
+        match connection.as_deref() {
+            Some("close") => false,
+            Some("keep-alive") => true,
+            _ => self.version != "HTTP/1.0",
+        }
+    }
+
+    /// Reads the remainder of the body
+    pub fn body(&mut self) -> Result<Vec<u8>> {
+        let mut body = vec![];
+        self.read_to_end(&mut body)?;
+        Ok(body)
+    }
+
+    /// Starts next chunk, returning its size, or reads trailers after the last one
+    fn next_chunk(&mut self) -> Result<usize> {
+        let line = read_line(&mut self.reader)?;
+        // chunk extensions are separated by `;`
+        let size = line.split(';').next().unwrap_or("").trim();
+
+        let size = match usize::from_str_radix(size, 16) {
+            Ok(size) => size,
+            Err(_) => return Err(invalid("Invalid chunk size")),
+        };
+
+        if size == 0 {
+            read_headers(&mut self.reader, &mut self.trailers)?;
+        }
+
+        Ok(size)
+    }
+}
+
+impl<R> Read for Response<R>
+where
+    R: BufRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self.framing {
+            Framing::Length(0) => Ok(0),
+            Framing::Length(remaining) => {
+                let max = remaining.min(buf.len());
+                let len = self.reader.read(&mut buf[..max])?;
+                if len == 0 {
+                    return Err(Error::new(ErrorKind::UnexpectedEof, "body is truncated"));
+                }
+
+                self.framing = Framing::Length(remaining - len);
+                Ok(len)
+            }
+            Framing::Chunked { done: true, .. } => Ok(0),
+            Framing::Chunked { remaining, .. } => {
+                let remaining = match remaining {
+                    0 => self.next_chunk()?,
+                    remaining => remaining,
+                };
+
+                if remaining == 0 {
+                    self.framing = Framing::Chunked {
+                        remaining,
+                        done: true,
+                    };
+                    return Ok(0);
+                }
+
+                let max = remaining.min(buf.len());
+                let len = self.reader.read(&mut buf[..max])?;
+                if len == 0 {
+                    return Err(Error::new(ErrorKind::UnexpectedEof, "chunk is truncated"));
+                }
+
+                if remaining == len {
+                    // chunk data is followed by CRLF
+                    read_line(&mut self.reader)?;
+                }
+
+                self.framing = Framing::Chunked {
+                    remaining: remaining - len,
+                    done: false,
+                };
+                Ok(len)
+            }
+            Framing::Close => self.reader.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_content_length() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n[]HTTP/1.1 204 No Content\r\n\r\n";
+        let mut reader = BufReader::new(&data[..]);
+
+        let mut response = Response::read(&mut reader).unwrap();
+        assert_eq!(200, response.status());
+        assert_eq!(b"[]".to_vec(), response.body().unwrap());
+        assert!(response.keep_alive());
+
+        let mut response = Response::read(&mut reader).unwrap();
+        assert_eq!(204, response.status());
+        assert!(response.body().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_chunked() {
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            4\r\n[{\"a\r\n6;ext=1\r\n\": 1}]\r\n0\r\nX-Trailer: yes\r\n\r\n\
+            HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        let mut reader = BufReader::new(&data[..]);
+
+        let mut response = Response::read(&mut reader).unwrap();
+        assert_eq!(b"[{\"a\": 1}]".to_vec(), response.body().unwrap());
+        assert_eq!(
+            Some(String::from("yes")),
+            response.trailers().get_first(String::from("x-trailer"))
+        );
+        assert!(response.keep_alive());
+
+        let response = Response::read(&mut reader).unwrap();
+        assert_eq!(200, response.status());
+    }
+
+    #[test]
+    fn test_read_to_close() {
+        let data = b"HTTP/1.0 200 OK\r\n\r\n{}";
+        let mut response = Response::read(BufReader::new(&data[..])).unwrap();
+
+        assert!(!response.keep_alive());
+        assert_eq!(b"{}".to_vec(), response.body().unwrap());
+    }
+
+    #[test]
+    fn test_error_status() {
+        let data = b"HTTP/1.1 404 Not Found\r\nContent-Length: 14\r\n\r\n{\"message\":\"\"}";
+        let mut response = Response::read(BufReader::new(&data[..])).unwrap();
+
+        assert_eq!(404, response.status());
+        assert_eq!(b"{\"message\":\"\"}".to_vec(), response.body().unwrap());
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nabc";
+        let mut response = Response::read(BufReader::new(&data[..])).unwrap();
+
+        assert!(response.body().is_err());
+    }
+}
@@ -13,6 +13,51 @@ pub enum JsVal {
     Object(HashMap<String, JsVal>),
 }
 
+impl JsVal {
+    /// Member of an object, `None` for other values
+    pub fn get(&self, key: &str) -> Option<&JsVal> {
+        match self {
+            JsVal::Object(obj) => obj.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsVal::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsVal::Int(nr) => Some(*nr),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsVal::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<JsVal>> {
+        match self {
+            JsVal::Array(ary) => Some(ary),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&HashMap<String, JsVal>> {
+        match self {
+            JsVal::Object(obj) => Some(obj),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum JsErr {
     InvalidJson(String),
@@ -36,7 +81,7 @@ pub struct Parser<'a> {
 type JsResult = Result<JsVal, JsErr>;
 
 impl<'a> Parser<'a> {
-    fn init(data: &Vec<u8>) -> Parser {
+    fn init(data: &Vec<u8>) -> Parser<'_> {
         Parser { data, idx: 0 }
     }
 
@@ -49,7 +94,7 @@ impl<'a> Parser<'a> {
     }
 
     fn has(&mut self, n: usize) -> bool {
-        return self.idx + n < self.data.len();
+        self.idx + n < self.data.len()
     }
 
     fn slice(&mut self, n: usize) -> &[u8] {
@@ -62,38 +107,38 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_literal(&mut self) -> JsResult {
-        if 't' == self.char() && self.has(3) {
-            if self.data[self.idx + 1] as char == 'r'
-                && self.data[self.idx + 2] as char == 'u'
-                && self.data[self.idx + 3] as char == 'e'
-            {
-                self.skip(4);
-                return Ok(JsVal::Bool(true));
-            }
+        if 't' == self.char()
+            && self.has(3)
+            && self.data[self.idx + 1] as char == 'r'
+            && self.data[self.idx + 2] as char == 'u'
+            && self.data[self.idx + 3] as char == 'e'
+        {
+            self.skip(4);
+            return Ok(JsVal::Bool(true));
         }
 
-        if 'f' == self.char() && self.has(4) {
-            if self.data[self.idx + 1] as char == 'a'
-                && self.data[self.idx + 2] as char == 'l'
-                && self.data[self.idx + 3] as char == 's'
-                && self.data[self.idx + 4] as char == 'e'
-            {
-                self.skip(5);
-                return Ok(JsVal::Bool(false));
-            }
+        if 'f' == self.char()
+            && self.has(4)
+            && self.data[self.idx + 1] as char == 'a'
+            && self.data[self.idx + 2] as char == 'l'
+            && self.data[self.idx + 3] as char == 's'
+            && self.data[self.idx + 4] as char == 'e'
+        {
+            self.skip(5);
+            return Ok(JsVal::Bool(false));
         }
 
-        if 'n' == self.char() && self.has(3) {
-            if (self.data[self.idx + 1]) as char == 'u'
-                && (self.data[self.idx + 2]) as char == 'l'
-                && (self.data[self.idx + 3]) as char == 'l'
-            {
-                self.skip(4);
-                return Ok(JsVal::Null);
-            }
+        if 'n' == self.char()
+            && self.has(3)
+            && (self.data[self.idx + 1]) as char == 'u'
+            && (self.data[self.idx + 2]) as char == 'l'
+            && (self.data[self.idx + 3]) as char == 'l'
+        {
+            self.skip(4);
+            return Ok(JsVal::Null);
         }
 
-        return Err(JsErr::InvalidJson(String::from("Invalid literal")));
+        Err(JsErr::InvalidJson(String::from("Invalid literal")))
     }
 
     fn parse_number(&mut self) -> JsResult {
@@ -140,10 +185,10 @@ impl<'a> Parser<'a> {
             };
         }
 
-        return match number.parse::<i64>() {
+        match number.parse::<i64>() {
             Ok(nr) => Ok(JsVal::Int(nr)),
             Err(_) => Err(JsErr::ParseError(number)),
-        };
+        }
     }
 
     fn parse_string(&mut self) -> JsResult {
@@ -166,7 +211,7 @@ impl<'a> Parser<'a> {
                             self.skip(1)
                         }
                         'b' => {
-                            string.push(08 as char);
+                            string.push(8 as char);
                             self.skip(1)
                         }
                         'f' => {
@@ -221,7 +266,7 @@ impl<'a> Parser<'a> {
             }
         }
 
-        return Err(JsErr::InvalidJson(String::from("Invalid string")));
+        Err(JsErr::InvalidJson(String::from("Invalid string")))
     }
 
     fn parse_any(&mut self) -> JsResult {
@@ -318,16 +363,21 @@ impl<'a> Parser<'a> {
             }
         }
 
-        return Err(JsErr::InvalidJson(String::from("Invalid JSON")));
+        Err(JsErr::InvalidJson(String::from("Invalid JSON")))
     }
 
     pub fn parse(data: Vec<u8>) -> JsResult {
         let mut parser = Parser::init(&data);
-        return parser.parse_any();
+        parser.parse_any()
     }
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::assertions_on_constants,
+    clippy::get_first,
+    clippy::single_match
+)]
 mod test {
     use super::*;
 
@@ -505,6 +555,19 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_accessors() {
+        let json = "{\"a\": [1, \"b\", true]}".as_bytes().to_vec();
+        let data = Parser::parse(json).unwrap();
+        let ary = data.get("a").and_then(|a| a.as_array()).unwrap();
+
+        assert_eq!(Some(1), ary[0].as_i64());
+        assert_eq!(Some("b"), ary[1].as_str());
+        assert_eq!(Some(true), ary[2].as_bool());
+        assert_eq!(None, ary[2].as_str());
+        assert!(data.get("b").is_none());
+    }
+
     #[test]
     fn test_bbbb() {
         let json = "[true, false, null]".as_bytes().to_vec();
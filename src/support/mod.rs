@@ -1,5 +1,7 @@
 mod header_bag;
+mod http;
 mod json;
 
-pub(crate) use header_bag::HeadersBag;
+pub use header_bag::HeadersBag;
+pub use http::{write_request, Response};
 pub use json::{JsErr, JsVal, Parser};